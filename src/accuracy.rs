@@ -0,0 +1,295 @@
+use anyhow::Result;
+use iso8601_timestamp::Timestamp;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use tracing::{error, info, warn};
+
+use crate::get_prices;
+use crate::rest_client::PriceData;
+use crate::secret::SecretString;
+use crate::watch::{duration_until_next_interval, unix_now, CURRENT_INTERVAL, INTERVAL_SECONDS};
+
+/// Interval type Amber uses for intervals that have finished and have a final price.
+pub const ACTUAL_INTERVAL: &str = "ActualInterval";
+
+/// Name of the file snapshots are appended to, one JSON record per line.
+const STORE_FILE: &str = "forecasts.jsonl";
+
+/// Struct type for one interval of a snapshot, as stored on disk.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredInterval {
+    /// Start of the current interval when the snapshot was taken.
+    pub recorded_for: Timestamp,
+    pub site_id: String,
+    pub channel_type: String,
+    pub interval_type: String,
+    pub start_time: Timestamp,
+    /// How many intervals ahead of the current interval this one is, negative for actuals.
+    pub lead_intervals: i64,
+    pub per_kwh: f32,
+    pub spot_per_kwh: f32,
+    pub spike_status: String,
+}
+
+/// Struct type for the local store of forecast snapshots.
+#[derive(Debug, Clone)]
+pub struct ForecastStore {
+    pub dir: PathBuf,
+}
+
+/// Default data directory, "$XDG_DATA_HOME/amber-cli" falling back to "$HOME/.local/share/amber-cli".
+pub fn default_data_dir() -> Option<PathBuf> {
+    let data_home = match env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?)
+            .join(".local")
+            .join("share"),
+    };
+    Some(data_home.join("amber-cli"))
+}
+
+/// Turn a price response into stored intervals, with lead times counted from the current interval.
+pub fn snapshot(prices: &[PriceData], site_id: &str) -> Vec<StoredInterval> {
+    let current = prices
+        .iter()
+        .find(|interval| interval.interval_type == CURRENT_INTERVAL);
+    let recorded_for = match current {
+        Some(current) => current.start_time,
+        None => return Vec::new(),
+    };
+
+    prices
+        .iter()
+        .map(|interval| StoredInterval {
+            recorded_for,
+            site_id: site_id.to_string(),
+            channel_type: interval.channel_type.clone(),
+            interval_type: interval.interval_type.clone(),
+            start_time: interval.start_time,
+            lead_intervals: (interval
+                .start_time
+                .duration_since(recorded_for)
+                .as_seconds_f64()
+                / INTERVAL_SECONDS as f64)
+                .round() as i64,
+            per_kwh: interval.per_kwh,
+            spot_per_kwh: interval.spot_per_kwh,
+            spike_status: interval.spike_status.clone(),
+        })
+        .collect()
+}
+
+impl ForecastStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn path(&self) -> PathBuf {
+        self.dir.join(STORE_FILE)
+    }
+
+    /// Append a snapshot to the store.
+    pub fn append(&self, intervals: &[StoredInterval]) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path())?;
+        for interval in intervals {
+            writeln!(file, "{}", serde_json::to_string(interval)?)?;
+        }
+        Ok(())
+    }
+
+    /// Read every stored interval for a site, lines that can not be read are skipped.
+    pub fn load(&self, site_id: &str) -> Result<Vec<StoredInterval>> {
+        let contents = match fs::read_to_string(self.path()) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(error.into()),
+        };
+        Ok(contents
+            .lines()
+            .filter_map(|line| match serde_json::from_str::<StoredInterval>(line) {
+                Ok(interval) => Some(interval),
+                Err(error) => {
+                    warn!("Skipping unreadable forecast record: {}", error);
+                    None
+                }
+            })
+            .filter(|interval| interval.site_id == site_id)
+            .collect())
+    }
+}
+
+/// Struct type for how accurate forecasts made a number of intervals ahead were.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LeadTimeAccuracy {
+    pub lead_intervals: i64,
+    pub lead_minutes: i64,
+    /// Number of forecasts compared against an actual price.
+    pub samples: usize,
+    /// Mean absolute error of `perKwh` in c/kWh.
+    pub mean_absolute_error: f32,
+    /// Mean of forecast less actual `perKwh`, above zero when forecasts ran high.
+    pub bias: f32,
+    pub spot_mean_absolute_error: f32,
+    pub spot_bias: f32,
+    /// Actual intervals that were a spike.
+    pub actual_spikes: usize,
+    /// Actual spikes that were forecast as a potential spike or spike.
+    pub spike_hits: usize,
+    pub spike_hit_rate: Option<f32>,
+    /// Forecast spikes that did not happen.
+    pub false_alarms: usize,
+}
+
+/// Struct type for running totals of one lead time.
+#[derive(Debug, Default)]
+struct LeadTotals {
+    samples: usize,
+    absolute_error: f32,
+    error: f32,
+    spot_absolute_error: f32,
+    spot_error: f32,
+    actual_spikes: usize,
+    spike_hits: usize,
+    false_alarms: usize,
+}
+
+fn is_spike(spike_status: &str) -> bool {
+    spike_status == "spike"
+}
+
+fn forecast_spike(spike_status: &str) -> bool {
+    spike_status == "potential" || spike_status == "spike"
+}
+
+/// Compare stored forecasts of a channel with the actual prices, grouped by lead time.
+pub fn forecast_accuracy(stored: &[StoredInterval], channel_type: &str) -> Vec<LeadTimeAccuracy> {
+    let stored: Vec<&StoredInterval> = stored
+        .iter()
+        .filter(|interval| interval.channel_type == channel_type)
+        .collect();
+
+    let actuals: HashMap<Timestamp, &StoredInterval> = stored
+        .iter()
+        .filter(|interval| interval.interval_type == ACTUAL_INTERVAL)
+        .map(|interval| (interval.start_time, *interval))
+        .collect();
+
+    // A snapshot taken twice in the same interval only counts once, the latest wins.
+    let forecasts: HashMap<(Timestamp, Timestamp), &StoredInterval> = stored
+        .iter()
+        .filter(|interval| interval.interval_type != ACTUAL_INTERVAL)
+        .map(|interval| ((interval.recorded_for, interval.start_time), *interval))
+        .collect();
+
+    let mut leads: BTreeMap<i64, LeadTotals> = BTreeMap::new();
+    for forecast in forecasts.values() {
+        let actual = match actuals.get(&forecast.start_time) {
+            Some(actual) => actual,
+            None => continue,
+        };
+        let totals = leads.entry(forecast.lead_intervals).or_default();
+        let error = forecast.per_kwh - actual.per_kwh;
+        let spot_error = forecast.spot_per_kwh - actual.spot_per_kwh;
+        totals.samples += 1;
+        totals.absolute_error += error.abs();
+        totals.error += error;
+        totals.spot_absolute_error += spot_error.abs();
+        totals.spot_error += spot_error;
+        match (
+            is_spike(&actual.spike_status),
+            forecast_spike(&forecast.spike_status),
+        ) {
+            (true, true) => {
+                totals.actual_spikes += 1;
+                totals.spike_hits += 1;
+            }
+            (true, false) => totals.actual_spikes += 1,
+            (false, true) => totals.false_alarms += 1,
+            (false, false) => (),
+        }
+    }
+
+    leads
+        .into_iter()
+        .map(|(lead_intervals, totals)| {
+            let samples = totals.samples as f32;
+            LeadTimeAccuracy {
+                lead_intervals,
+                lead_minutes: lead_intervals * (INTERVAL_SECONDS / 60) as i64,
+                samples: totals.samples,
+                mean_absolute_error: totals.absolute_error / samples,
+                bias: totals.error / samples,
+                spot_mean_absolute_error: totals.spot_absolute_error / samples,
+                spot_bias: totals.spot_error / samples,
+                actual_spikes: totals.actual_spikes,
+                spike_hits: totals.spike_hits,
+                spike_hit_rate: match totals.actual_spikes {
+                    0 => None,
+                    spikes => Some(totals.spike_hits as f32 / spikes as f32),
+                },
+                false_alarms: totals.false_alarms,
+            }
+        })
+        .collect()
+}
+
+/// Fetch the forecast, and the last actual interval, and append it to the store.
+#[tracing::instrument(level = "debug", skip(auth_token, store))]
+pub async fn record_forecast(
+    base_url: String,
+    auth_token: SecretString,
+    site_id: String,
+    forecast_intervals: u32,
+    store: &ForecastStore,
+) -> Result<usize> {
+    // The previous interval is the one that just finished, so every snapshot adds its actual price.
+    let window = format!("current?next={}&previous=1", forecast_intervals);
+    let prices = get_prices(base_url, auth_token, site_id.clone(), window).await?;
+    let intervals = snapshot(&prices, &site_id);
+    store.append(&intervals)?;
+    Ok(intervals.len())
+}
+
+/// Record a snapshot every interval, or once when `once` is set, for example from cron.
+/// Errors talking to the API are logged and retried on the next interval.
+#[tracing::instrument(level = "debug", skip(auth_token, store))]
+pub async fn run_recorder(
+    base_url: String,
+    auth_token: SecretString,
+    site_id: String,
+    forecast_intervals: u32,
+    once: bool,
+    store: &ForecastStore,
+) -> Result<()> {
+    loop {
+        match record_forecast(
+            base_url.clone(),
+            auth_token.clone(),
+            site_id.clone(),
+            forecast_intervals,
+            store,
+        )
+        .await
+        {
+            Ok(recorded) => info!("Recorded {} intervals to {}", recorded, store.dir.display()),
+            Err(record_error) if once => return Err(record_error),
+            Err(record_error) => error!("Failed to record the forecast: {}", record_error),
+        }
+
+        if once {
+            return Ok(());
+        }
+
+        tokio::time::sleep(duration_until_next_interval(unix_now())).await;
+    }
+}
@@ -1,16 +1,51 @@
-use config::{Config, ConfigError, File};
-use serde::Deserialize;
+use anyhow::Context;
+use config::{Config, ConfigError, Environment, File, Map, Source, Value};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::io::BufRead;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use tracing::warn;
+
+use crate::secret::SecretString;
+
+/// System wide config file, the last place looked for one.
+pub const SYSTEM_CONFIG_FILE: &str = "/etc/amber-cli/config.toml";
+
+/// Prefix of the environment variables that override config values, e.g. `AMBER_APITOKEN__PSK`.
+const ENV_PREFIX: &str = "AMBER";
+
+/// Service name API tokens are stored under in the OS secret service.
+pub const KEYRING_SERVICE: &str = "amber-cli";
+
+/// Amber's versioned API, written to new config files.
+pub const DEFAULT_BASE_URL: &str = "https://api.amber.com.au/v1";
+
+/// States and territories Amber publishes renewables data for, as used in `userconfig.state`.
+pub const STATES: [&str; 8] = ["nsw", "vic", "qld", "sa", "tas", "act", "nt", "wa"];
 #[derive(Debug, Deserialize)]
 #[allow(unused)]
 pub struct AmberConfig {
     pub base_url: String,
 }
 
+/// API token, the PSK is either set in the config file or read from one of the other sources.
 #[derive(Debug, Deserialize)]
 #[allow(unused)]
 pub struct ApiToken {
     pub name: String,
-    pub psk: String,
+    #[serde(default)]
+    pub psk: SecretString,
+    /// File holding the PSK, e.g. a Docker secret in "/run/secrets/amber", or "-" for stdin.
+    pub psk_file: Option<String>,
+    /// Command that prints the PSK, e.g. "pass show amber".
+    pub psk_command: Option<String>,
+    /// Read the PSK from the OS secret service, stored under the token `name`.
+    #[serde(default)]
+    pub keyring: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -19,20 +54,503 @@ pub struct AppConfig {
     pub amberconfig: AmberConfig,
     pub userconfig: UserConfig,
     pub apitoken: ApiToken,
+    #[serde(default)]
+    pub webhook: Vec<WebhookConfig>,
+    pub mqtt: Option<MqttConfig>,
+    pub influxdb: Option<InfluxConfig>,
+    pub billing: Option<BillingConfig>,
+    pub emissions: Option<EmissionsConfig>,
 }
 
 #[derive(Debug, Deserialize)]
 #[allow(unused)]
 pub struct UserConfig {
     pub state: String,
+    /// Site to query, defaults to the first site on the account.
+    pub site_id: Option<String>,
+}
+
+/// Webhook endpoint that watch mode alerts (and optionally interval updates) are POSTed to.
+#[derive(Debug, Deserialize, Clone)]
+#[allow(unused)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Extra headers, such as `Authorization`, their values are redacted like the API token.
+    #[serde(default)]
+    pub headers: HashMap<String, SecretString>,
+    /// Shared secret used to sign the payload with HMAC-SHA256.
+    pub secret: Option<SecretString>,
+    #[serde(default = "default_webhook_timeout_seconds")]
+    pub timeout_seconds: u64,
+    #[serde(default = "default_webhook_retries")]
+    pub retries: u32,
+    /// Also POST the current interval data after every poll, not just alerts.
+    #[serde(default)]
+    pub on_interval: bool,
+}
+
+fn default_webhook_timeout_seconds() -> u64 {
+    10
+}
+
+fn default_webhook_retries() -> u32 {
+    3
+}
+
+/// MQTT broker that watch mode publishes prices, renewables and spike status to.
+#[derive(Debug, Deserialize, Clone)]
+#[allow(unused)]
+pub struct MqttConfig {
+    pub host: String,
+    #[serde(default = "default_mqtt_port")]
+    pub port: u16,
+    #[serde(default = "default_mqtt_client_id")]
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<SecretString>,
+    #[serde(default = "default_mqtt_topic_prefix")]
+    pub topic_prefix: String,
+    /// MQTT quality of service level, 0, 1 or 2.
+    #[serde(default = "default_mqtt_qos")]
+    pub qos: u8,
+    #[serde(default = "default_mqtt_retain")]
+    pub retain: bool,
+    #[serde(default)]
+    pub tls: bool,
+    /// PEM encoded CA certificate, defaults to the system's trusted roots when using TLS.
+    pub ca_file: Option<String>,
+    /// Publish Home Assistant MQTT discovery config when watch mode starts.
+    #[serde(default)]
+    pub discovery: bool,
+    #[serde(default = "default_mqtt_discovery_prefix")]
+    pub discovery_prefix: String,
+}
+
+fn default_mqtt_port() -> u16 {
+    1883
+}
+
+fn default_mqtt_client_id() -> String {
+    "amber-client".to_string()
+}
+
+fn default_mqtt_topic_prefix() -> String {
+    "amber".to_string()
+}
+
+fn default_mqtt_qos() -> u8 {
+    1
+}
+
+fn default_mqtt_retain() -> bool {
+    true
+}
+
+fn default_mqtt_discovery_prefix() -> String {
+    "homeassistant".to_string()
+}
+
+/// InfluxDB v2 server that usage exports and watch mode can write line protocol to.
+#[derive(Debug, Deserialize, Clone)]
+#[allow(unused)]
+pub struct InfluxConfig {
+    pub url: String,
+    pub org: String,
+    pub bucket: String,
+    pub token: SecretString,
+    #[serde(default = "default_influx_timeout_seconds")]
+    pub timeout_seconds: u64,
+}
+
+fn default_influx_timeout_seconds() -> u64 {
+    10
+}
+
+/// Fixed charges added to `report bill`, so it can be reconciled against an invoice.
+#[derive(Debug, Deserialize, Clone, Default)]
+#[allow(unused)]
+pub struct BillingConfig {
+    /// Daily supply charge in cents per day.
+    #[serde(default)]
+    pub daily_supply_charge: f32,
+    /// Amber membership fee in cents per month, spread evenly over the days billed.
+    #[serde(default)]
+    pub monthly_membership_fee: f32,
+}
+
+/// Emissions intensity used by `report emissions`, instead of the factor for your state.
+#[derive(Debug, Deserialize, Clone)]
+#[allow(unused)]
+pub struct EmissionsConfig {
+    /// Average kg CO2-e per kWh of power used from the grid.
+    pub intensity: f32,
+}
+
+/// Default config file, "$XDG_CONFIG_HOME/amber-cli/config.toml" falling back to "$HOME/.config/amber-cli/config.toml".
+pub fn default_config_path() -> Option<PathBuf> {
+    let config_home = match env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(config_home.join("amber-cli").join("config.toml"))
+}
+
+/// Config file named by the `AMBER_CONFIG` environment variable.
+pub fn env_config_path() -> Option<PathBuf> {
+    env::var_os("AMBER_CONFIG")
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Find the config file: `config_file` when given, then `$AMBER_CONFIG`, then the first of
+/// the default config file and "/etc/amber-cli/config.toml" that exists.
+pub fn find_config_file(config_file: Option<PathBuf>) -> Option<PathBuf> {
+    config_file.or_else(env_config_path).or_else(|| {
+        [
+            default_config_path(),
+            Some(PathBuf::from(SYSTEM_CONFIG_FILE)),
+        ]
+        .into_iter()
+        .flatten()
+        .find(|path| path.exists())
+    })
+}
+
+/// Describe where the config was read from, for error messages.
+fn config_source(app_config_file: Option<&str>, profile: Option<&str>) -> String {
+    let source = match app_config_file {
+        Some(app_config_file) => format!("config file {}", app_config_file),
+        None => "AMBER_* environment variables".to_string(),
+    };
+    match profile {
+        Some(profile) => format!("{} (profile {})", source, profile),
+        None => source,
+    }
+}
+
+/// Struct type for a config value table, such as a profile, layered over the config file.
+#[derive(Debug, Clone)]
+struct TableSource(Map<String, Value>);
+
+impl Source for TableSource {
+    fn clone_into_box(&self) -> Box<dyn Source + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    fn collect(&self) -> Result<Map<String, Value>, ConfigError> {
+        Ok(self.0.clone())
+    }
+}
+
+/// Struct type for a profile in the config file, as shown by `profiles list`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileSummary {
+    pub name: String,
+    pub token_name: Option<String>,
+    pub state: Option<String>,
+    pub site_id: Option<String>,
+    pub base_url: Option<String>,
+}
+
+/// Read the config file's `[profiles.<name>]` tables.
+fn read_profiles(file_config: &Config) -> Map<String, Value> {
+    file_config.get_table("profiles").unwrap_or_default()
+}
+
+/// Merge the config: the file's top level sections, then its `[default]` profile, then the
+/// `[profiles.<name>]` profile, then `AMBER_*` environment variables.
+fn merge_config(
+    app_config_file: Option<&str>,
+    profile: Option<&str>,
+) -> Result<Config, ConfigError> {
+    let mut file_builder = Config::builder();
+    if let Some(app_config_file) = app_config_file {
+        file_builder = file_builder.add_source(File::with_name(app_config_file));
+    }
+    let file_config = file_builder.build()?;
+
+    let mut builder = Config::builder().add_source(file_config.clone());
+    if let Ok(default) = file_config.get_table("default") {
+        builder = builder.add_source(TableSource(default));
+    }
+    if let Some(profile) = profile {
+        let mut profiles = read_profiles(&file_config);
+        let selected = match profiles.remove(profile).map(|value| value.into_table()) {
+            Some(Ok(selected)) => selected,
+            _ => {
+                let mut names: Vec<String> = profiles.into_keys().collect();
+                names.sort();
+                return Err(ConfigError::Message(format!(
+                    "No [profiles.{}] in {}, the profiles are: {}",
+                    profile,
+                    config_source(app_config_file, None),
+                    names.join(", ")
+                )));
+            }
+        };
+        builder = builder.add_source(TableSource(selected));
+    }
+    builder
+        .add_source(
+            Environment::with_prefix(ENV_PREFIX)
+                .prefix_separator("_")
+                .separator("__")
+                .try_parsing(true),
+        )
+        .build()
 }
 
 impl AppConfig {
-    pub async fn get(app_config_file: String) -> Result<Self, ConfigError> {
-        let config = Config::builder()
-            .add_source(File::with_name(&app_config_file))
-            .build()?;
+    /// Read and validate the config file, merging the `[default]` profile and the selected
+    /// profile, with any `AMBER_*` environment variable overrides.
+    pub async fn get(
+        app_config_file: Option<String>,
+        profile: Option<&str>,
+    ) -> Result<Self, ConfigError> {
+        let config = Self::load(app_config_file.as_deref(), profile)?;
+        let problems = config.problems();
+        if !problems.is_empty() {
+            return Err(ConfigError::Message(format!(
+                "Invalid {}:\n  {}",
+                config_source(app_config_file.as_deref(), profile),
+                problems.join("\n  ")
+            )));
+        }
+        Ok(config)
+    }
+
+    /// Read the config file without validating it, naming the file in any error.
+    ///
+    /// Every value can be overridden by an environment variable named after its section and key,
+    /// e.g. `AMBER_APITOKEN__PSK` for `psk` in `[apitoken]`, and without a file the whole config
+    /// comes from the environment.
+    pub fn load(app_config_file: Option<&str>, profile: Option<&str>) -> Result<Self, ConfigError> {
+        let config = merge_config(app_config_file, profile)?;
+
+        if let Some(warning) = app_config_file.and_then(|file| permissions_warning(Path::new(file)))
+        {
+            warn!("{}", warning);
+        }
+
+        let mut config: AppConfig = config.try_deserialize().map_err(|error| {
+            ConfigError::Message(format!(
+                "Failed to read {}: {}, see config.toml.example for every section",
+                config_source(app_config_file, profile),
+                error
+            ))
+        })?;
+        config.apitoken.psk = config.apitoken.read_psk().map_err(|error| {
+            ConfigError::Message(format!("Failed to read the API token: {:#}", error))
+        })?;
+        Ok(config)
+    }
+
+    /// List the profiles in the config file, with the `[default]` profile merged into each.
+    /// API tokens are not read, so no `psk_command` is run.
+    pub fn profiles(app_config_file: Option<&str>) -> Result<Vec<ProfileSummary>, ConfigError> {
+        let mut file_builder = Config::builder();
+        if let Some(app_config_file) = app_config_file {
+            file_builder = file_builder.add_source(File::with_name(app_config_file));
+        }
+        let mut names: Vec<String> = read_profiles(&file_builder.build()?).into_keys().collect();
+        names.sort();
+
+        names
+            .into_iter()
+            .map(|name| {
+                let config = merge_config(app_config_file, Some(&name))?;
+                Ok(ProfileSummary {
+                    token_name: config.get_string("apitoken.name").ok(),
+                    state: config.get_string("userconfig.state").ok(),
+                    site_id: config.get_string("userconfig.site_id").ok(),
+                    base_url: config.get_string("amberconfig.base_url").ok(),
+                    name,
+                })
+            })
+            .collect()
+    }
+
+    /// Check the values in the config file, returning a message for every problem found.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if let Err(problem) = check_url("amberconfig.base_url", &self.amberconfig.base_url, true) {
+            problems.push(problem);
+        }
+        if !STATES.contains(&self.userconfig.state.as_str()) {
+            problems.push(format!(
+                "userconfig.state \"{}\" must be one of {}",
+                self.userconfig.state,
+                STATES.join(", ")
+            ));
+        }
+        if let Err(problem) = check_psk(self.apitoken.psk.expose_secret()) {
+            problems.push(problem);
+        }
+
+        for webhook in &self.webhook {
+            if let Err(problem) = check_url("webhook.url", &webhook.url, false) {
+                problems.push(problem);
+            }
+        }
+        if let Some(mqtt) = &self.mqtt {
+            if mqtt.qos > 2 {
+                problems.push(format!("mqtt.qos {} must be 0, 1 or 2", mqtt.qos));
+            }
+            if mqtt.password.is_some() && mqtt.username.is_none() {
+                problems.push("mqtt.password is set without a mqtt.username".to_string());
+            }
+        }
+        if let Some(influxdb) = &self.influxdb {
+            if let Err(problem) = check_url("influxdb.url", &influxdb.url, false) {
+                problems.push(problem);
+            }
+        }
+        if let Some(billing) = &self.billing {
+            if billing.daily_supply_charge < 0.0 || billing.monthly_membership_fee < 0.0 {
+                problems.push("billing charges must not be negative".to_string());
+            }
+        }
+        if let Some(emissions) = &self.emissions {
+            if emissions.intensity < 0.0 {
+                problems.push("emissions.intensity must not be negative".to_string());
+            }
+        }
+        problems
+    }
+}
+
+/// Check a URL parses, and uses https when `https_only` is set.
+fn check_url(key: &str, value: &str, https_only: bool) -> Result<(), String> {
+    let url = Url::parse(value)
+        .map_err(|error| format!("{} \"{}\" is not a valid URL: {}", key, value, error))?;
+    match url.scheme() {
+        "https" => Ok(()),
+        "http" if !https_only => Ok(()),
+        scheme => Err(format!(
+            "{} \"{}\" must be an https URL, not {}",
+            key, value, scheme
+        )),
+    }
+}
+
+/// Sanity check an API key looks like the "psk_..." keys Amber issues.
+pub fn check_psk(psk: &str) -> Result<(), String> {
+    match psk.strip_prefix("psk_") {
+        Some(key) if !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric()) => Ok(()),
+        Some(_) => {
+            Err("apitoken.psk must only have letters and numbers after \"psk_\"".to_string())
+        }
+        None => Err(
+            "apitoken.psk does not look like an Amber API key, they start with \"psk_\""
+                .to_string(),
+        ),
+    }
+}
+
+impl ApiToken {
+    /// Read the PSK from whichever source the config file sets, only one may be set.
+    pub fn read_psk(&self) -> anyhow::Result<SecretString> {
+        let sources = [
+            !self.psk.is_empty(),
+            self.psk_file.is_some(),
+            self.psk_command.is_some(),
+            self.keyring,
+        ];
+        match sources.iter().filter(|set| **set).count() {
+            0 => anyhow::bail!("Set one of psk, psk_file, psk_command or keyring in [apitoken]"),
+            1 => (),
+            _ => {
+                anyhow::bail!("Only set one of psk, psk_file, psk_command or keyring in [apitoken]")
+            }
+        }
+
+        let psk = if !self.psk.is_empty() {
+            self.psk.expose_secret().to_string()
+        } else if let Some(psk_file) = &self.psk_file {
+            read_psk_file(psk_file)?
+        } else if let Some(psk_command) = &self.psk_command {
+            run_psk_command(psk_command)?
+        } else {
+            off_runtime(|| keyring_entry(&self.name)?.get_password()).map_err(|error| {
+                anyhow::anyhow!(
+                    "No API token named {} in the OS secret service ({}), add it with `config store-token`",
+                    self.name,
+                    error
+                )
+            })?
+        };
+        Ok(SecretString::from(psk.trim()))
+    }
+}
+
+/// Read a PSK from a file, or from the first line of stdin for "-".
+fn read_psk_file(psk_file: &str) -> anyhow::Result<String> {
+    if psk_file == "-" {
+        let mut psk = String::new();
+        std::io::stdin().lock().read_line(&mut psk)?;
+        return Ok(psk);
+    }
+    std::fs::read_to_string(psk_file)
+        .with_context(|| format!("Failed to read psk_file {}", psk_file))
+}
+
+/// Run a command through the shell and use what it prints as the PSK.
+fn run_psk_command(psk_command: &str) -> anyhow::Result<String> {
+    let output = if cfg!(windows) {
+        Command::new("cmd").args(["/C", psk_command]).output()?
+    } else {
+        Command::new("sh").args(["-c", psk_command]).output()?
+    };
+    if !output.status.success() {
+        anyhow::bail!(
+            "psk_command \"{}\" failed with {}: {}",
+            psk_command,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// The OS secret service entry an API token is stored in.
+pub fn keyring_entry(token_name: &str) -> keyring::Result<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, token_name)
+}
+
+/// Store an API token in the OS secret service, for configs with `keyring = true`.
+pub fn store_keyring_psk(token_name: &str, psk: &SecretString) -> anyhow::Result<()> {
+    check_psk(psk.expose_secret()).map_err(|problem| anyhow::anyhow!(problem))?;
+    off_runtime(|| keyring_entry(token_name)?.set_password(psk.expose_secret()))
+        .context("Failed to store the API token in the OS secret service")
+}
+
+/// Run a blocking keyring call on its own thread. The secret service backend blocks on its own
+/// tokio runtime, which panics when called from a thread already driving one.
+fn off_runtime<T: Send>(call: impl FnOnce() -> keyring::Result<T> + Send) -> keyring::Result<T> {
+    std::thread::scope(|scope| match scope.spawn(call).join() {
+        Ok(result) => result,
+        Err(panic) => std::panic::resume_unwind(panic),
+    })
+}
 
-        config.try_deserialize()
+/// Warn when a config file can be read by other users, as it may hold the API token.
+pub fn permissions_warning(path: &Path) -> Option<String> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(path).ok()?.permissions().mode() & 0o777;
+        if mode & 0o077 != 0 {
+            return Some(format!(
+                "Config file {} can be read by other users (mode {:o}), run: chmod 600 {}",
+                path.display(),
+                mode,
+                path.display()
+            ));
+        }
     }
+    #[cfg(not(unix))]
+    let _ = path;
+    None
 }
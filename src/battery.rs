@@ -0,0 +1,230 @@
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use iso8601_timestamp::Timestamp;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::bill::{parse_report_date, FEED_IN_CHANNEL};
+use crate::get_usage_by_date;
+use crate::rest_client::UsageData;
+use crate::secret::SecretString;
+
+/// Channel Amber reports household usage on, the only channel a battery can supply.
+const GENERAL_CHANNEL: &str = "general";
+
+/// Enum type for when the simulated battery charges and discharges.
+#[derive(ValueEnum, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum Strategy {
+    /// Store excess solar and use it to cover household usage.
+    SelfConsumption,
+    /// Also charge from the grid below `charge_below`, and only discharge above `discharge_above`,
+    /// exporting when the feed in price is above it too.
+    Arbitrage,
+    /// Charge from solar and cheap grid power, only discharge during price spikes, exporting what is left.
+    SpikeExport,
+}
+
+/// Struct type holding the battery and how it is run.
+#[derive(Debug, Clone)]
+pub struct BatteryOptions {
+    pub capacity_kwh: f32,
+    pub power_kw: f32,
+    /// Round trip efficiency, 0.9 means 90% of the energy charged can be discharged.
+    pub efficiency: f32,
+    pub strategy: Strategy,
+    /// Price in c/kWh to charge from the grid at or below.
+    pub charge_below: f32,
+    /// Price in c/kWh to discharge at or above.
+    pub discharge_above: f32,
+    /// Installed cost in dollars, used to work out the payback period.
+    pub battery_cost: Option<f32>,
+}
+
+/// Struct type for the result of replaying usage with a battery, costs are in cents.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BatterySimulation {
+    pub strategy: Strategy,
+    pub days: usize,
+    pub import_kwh_without_battery: f32,
+    pub import_kwh_with_battery: f32,
+    pub export_kwh_without_battery: f32,
+    pub export_kwh_with_battery: f32,
+    pub cost_without_battery: f32,
+    pub cost_with_battery: f32,
+    pub savings: f32,
+    /// Savings scaled up to a year, in dollars.
+    pub annual_savings: f32,
+    pub charged_kwh: f32,
+    pub discharged_kwh: f32,
+    /// Full equivalent cycles, the energy discharged divided by the capacity.
+    pub cycles: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payback_years: Option<f32>,
+}
+
+/// Struct type for one interval of household usage and export.
+#[derive(Debug, Default)]
+struct Interval {
+    duration: u8,
+    import_kwh: f32,
+    import_per_kwh: f32,
+    export_kwh: f32,
+    /// Amber's feed in price, negative when exporting earns money.
+    export_per_kwh: f32,
+    spike: bool,
+}
+
+impl Interval {
+    fn cost(&self, import_kwh: f32, export_kwh: f32) -> f32 {
+        import_kwh * self.import_per_kwh + export_kwh * self.export_per_kwh
+    }
+}
+
+/// Combine the general and feed in usage records into one interval per start time.
+fn intervals(usage: &[UsageData]) -> Vec<Interval> {
+    let mut intervals: BTreeMap<Timestamp, Interval> = BTreeMap::new();
+    for record in usage {
+        let interval = intervals.entry(record.start_time).or_default();
+        interval.duration = record.duration;
+        match record.channel_type.as_str() {
+            GENERAL_CHANNEL => {
+                interval.import_kwh += record.kwh;
+                interval.import_per_kwh = record.per_kwh;
+                interval.spike = record.spike_status == "spike";
+            }
+            FEED_IN_CHANNEL => {
+                interval.export_kwh += record.kwh;
+                interval.export_per_kwh = record.per_kwh;
+            }
+            // Controlled load is on its own meter, a battery does not change it.
+            _ => (),
+        }
+    }
+    intervals.into_values().collect()
+}
+
+/// Replay usage intervals with a battery, interval by interval, starting empty.
+pub fn simulate_battery(usage: &[UsageData], options: &BatteryOptions) -> BatterySimulation {
+    let mut state_of_charge = 0.0_f32;
+    let mut charged_kwh = 0.0;
+    let mut discharged_kwh = 0.0;
+    let mut import_kwh_with_battery = 0.0;
+    let mut export_kwh_with_battery = 0.0;
+    let mut cost_without_battery = 0.0;
+    let mut cost_with_battery = 0.0;
+
+    let intervals = intervals(usage);
+    for interval in &intervals {
+        let max_energy = options.power_kw * f32::from(interval.duration) / 60.0;
+        let mut import_kwh = interval.import_kwh;
+        let mut export_kwh = interval.export_kwh;
+
+        // Excess solar always goes into the battery first, losses are taken when charging.
+        let room = (options.capacity_kwh - state_of_charge) / options.efficiency;
+        let solar_charge = export_kwh.min(max_energy).min(room);
+        export_kwh -= solar_charge;
+        state_of_charge += solar_charge * options.efficiency;
+        let mut charge = solar_charge;
+
+        let (discharge_to_home, discharge_to_grid) = match options.strategy {
+            Strategy::SelfConsumption => (true, false),
+            Strategy::Arbitrage => (
+                interval.import_per_kwh >= options.discharge_above,
+                -interval.export_per_kwh >= options.discharge_above,
+            ),
+            Strategy::SpikeExport => (interval.spike, interval.spike),
+        };
+
+        let mut discharge = 0.0;
+        if discharge_to_home {
+            discharge = import_kwh.min(max_energy).min(state_of_charge);
+            import_kwh -= discharge;
+            state_of_charge -= discharge;
+        }
+        if discharge_to_grid {
+            let to_grid = (max_energy - discharge).min(state_of_charge);
+            export_kwh += to_grid;
+            state_of_charge -= to_grid;
+            discharge += to_grid;
+        }
+
+        let grid_charging = options.strategy != Strategy::SelfConsumption
+            && discharge == 0.0
+            && interval.import_per_kwh <= options.charge_below;
+        if grid_charging {
+            let room = (options.capacity_kwh - state_of_charge) / options.efficiency;
+            let grid_charge = (max_energy - charge).min(room).max(0.0);
+            import_kwh += grid_charge;
+            state_of_charge += grid_charge * options.efficiency;
+            charge += grid_charge;
+        }
+
+        charged_kwh += charge;
+        discharged_kwh += discharge;
+        import_kwh_with_battery += import_kwh;
+        export_kwh_with_battery += export_kwh;
+        cost_without_battery += interval.cost(interval.import_kwh, interval.export_kwh);
+        cost_with_battery += interval.cost(import_kwh, export_kwh);
+    }
+
+    let days = usage
+        .iter()
+        .map(|record| record.date.date())
+        .collect::<BTreeSet<_>>()
+        .len();
+    let savings = cost_without_battery - cost_with_battery;
+    let annual_savings = match days {
+        0 => 0.0,
+        _ => savings / 100.0 * 365.0 / days as f32,
+    };
+    let payback_years = options
+        .battery_cost
+        .filter(|_| annual_savings > 0.0)
+        .map(|battery_cost| battery_cost / annual_savings);
+
+    BatterySimulation {
+        strategy: options.strategy,
+        days,
+        import_kwh_without_battery: intervals.iter().map(|i| i.import_kwh).sum(),
+        import_kwh_with_battery,
+        export_kwh_without_battery: intervals.iter().map(|i| i.export_kwh).sum(),
+        export_kwh_with_battery,
+        cost_without_battery,
+        cost_with_battery,
+        savings,
+        annual_savings,
+        charged_kwh,
+        discharged_kwh,
+        cycles: discharged_kwh / options.capacity_kwh,
+        payback_years,
+    }
+}
+
+/// Function to fetch usage for a date range and replay it with a battery.
+#[tracing::instrument(level = "debug", skip(auth_token))]
+pub async fn get_battery_simulation(
+    base_url: String,
+    auth_token: SecretString,
+    site_id: String,
+    start_date: String,
+    end_date: String,
+    options: BatteryOptions,
+) -> Result<BatterySimulation> {
+    if options.capacity_kwh <= 0.0 || options.power_kw <= 0.0 {
+        bail!("Battery capacity and power must be above zero");
+    }
+    if options.efficiency <= 0.0 || options.efficiency > 1.0 {
+        bail!("Battery efficiency must be between 0 and 1");
+    }
+    if parse_report_date(&end_date)? < parse_report_date(&start_date)? {
+        bail!(
+            "End date {} is before the start date {}",
+            end_date,
+            start_date
+        );
+    }
+    let usage = get_usage_by_date(base_url, auth_token, site_id, start_date, end_date).await?;
+    Ok(simulate_battery(&usage, &options))
+}
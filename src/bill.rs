@@ -0,0 +1,187 @@
+use anyhow::{anyhow, bail, Result};
+use chrono::NaiveDate;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::app_config::BillingConfig;
+use crate::get_usage_by_date;
+use crate::rest_client::UsageData;
+use crate::secret::SecretString;
+
+/// Channel Amber reports exported solar on, its cost is negative as it is a credit.
+pub const FEED_IN_CHANNEL: &str = "feedIn";
+
+/// Average number of days in a month, used to spread the monthly membership fee over days.
+pub const DAYS_PER_MONTH: f32 = 365.0 / 12.0;
+
+/// Struct type for the kWh and cost of a channel.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelTotals {
+    pub channel_type: String,
+    pub kwh: f32,
+    /// Cost in cents, negative for feed in credit.
+    pub cost: f32,
+}
+
+/// Struct type for the kWh and cost of a channel in one tariff period (peak, shoulder, offPeak...).
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PeriodTotals {
+    pub channel_type: String,
+    pub period: String,
+    pub kwh: f32,
+    pub cost: f32,
+}
+
+/// Struct type for the usage of a single day.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DayTotals {
+    pub date: String,
+    pub import_kwh: f32,
+    pub import_cost: f32,
+    pub feed_in_kwh: f32,
+    pub feed_in_credit: f32,
+    /// Import cost less feed in credit, without fixed charges.
+    pub net_cost: f32,
+}
+
+/// Struct type for an estimated bill, all costs are in cents.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Bill {
+    pub start_date: String,
+    pub end_date: String,
+    pub days: u32,
+    pub import_kwh: f32,
+    pub import_cost: f32,
+    pub feed_in_kwh: f32,
+    pub feed_in_credit: f32,
+    pub supply_charge: f32,
+    pub membership_fee: f32,
+    /// Import cost, less feed in credit, plus the supply charge and membership fee.
+    pub net_cost: f32,
+    pub channels: Vec<ChannelTotals>,
+    pub periods: Vec<PeriodTotals>,
+    pub daily: Vec<DayTotals>,
+}
+
+/// Total the kWh and cost of each channel.
+pub fn channel_totals(usage: &[UsageData]) -> Vec<ChannelTotals> {
+    let mut channels: BTreeMap<String, (f32, f32)> = BTreeMap::new();
+    for record in usage {
+        let channel = channels.entry(record.channel_type.clone()).or_default();
+        channel.0 += record.kwh;
+        channel.1 += record.cost;
+    }
+    channels
+        .into_iter()
+        .map(|(channel_type, (kwh, cost))| ChannelTotals {
+            channel_type,
+            kwh,
+            cost,
+        })
+        .collect()
+}
+
+/// Total up usage data into a bill for the days from `start_date` to `end_date` inclusive.
+pub fn build_bill(
+    usage: &[UsageData],
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    billing: Option<&BillingConfig>,
+) -> Bill {
+    let mut periods: BTreeMap<(String, String), (f32, f32)> = BTreeMap::new();
+    let mut daily: BTreeMap<String, DayTotals> = BTreeMap::new();
+
+    for record in usage {
+        let period = periods
+            .entry((
+                record.channel_type.clone(),
+                record.tariff_information.period.clone(),
+            ))
+            .or_default();
+        period.0 += record.kwh;
+        period.1 += record.cost;
+
+        let date = record.date.date().to_string();
+        let day = daily.entry(date.clone()).or_insert_with(|| DayTotals {
+            date,
+            ..Default::default()
+        });
+        if record.channel_type == FEED_IN_CHANNEL {
+            day.feed_in_kwh += record.kwh;
+            day.feed_in_credit -= record.cost;
+        } else {
+            day.import_kwh += record.kwh;
+            day.import_cost += record.cost;
+        }
+        day.net_cost += record.cost;
+    }
+
+    let daily: Vec<DayTotals> = daily.into_values().collect();
+    let import_kwh = daily.iter().map(|day| day.import_kwh).sum();
+    let import_cost = daily.iter().map(|day| day.import_cost).sum();
+    let feed_in_kwh = daily.iter().map(|day| day.feed_in_kwh).sum();
+    let feed_in_credit = daily.iter().map(|day| day.feed_in_credit).sum();
+
+    let days = (end_date - start_date).num_days().max(0) as u32 + 1;
+    let supply_charge = billing.map_or(0.0, |billing| billing.daily_supply_charge * days as f32);
+    let membership_fee = billing.map_or(0.0, |billing| {
+        billing.monthly_membership_fee / DAYS_PER_MONTH * days as f32
+    });
+
+    Bill {
+        start_date: start_date.to_string(),
+        end_date: end_date.to_string(),
+        days,
+        import_kwh,
+        import_cost,
+        feed_in_kwh,
+        feed_in_credit,
+        supply_charge,
+        membership_fee,
+        net_cost: import_cost - feed_in_credit + supply_charge + membership_fee,
+        channels: channel_totals(usage),
+        periods: periods
+            .into_iter()
+            .map(|((channel_type, period), (kwh, cost))| PeriodTotals {
+                channel_type,
+                period,
+                kwh,
+                cost,
+            })
+            .collect(),
+        daily,
+    }
+}
+
+/// Parse a yyyy-mm-dd date, returning an error rather than exiting like `parse_date_naive`.
+pub fn parse_report_date(date: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| anyhow!("Date {} must be in the format yyyy-mm-dd", date))
+}
+
+/// Function to fetch usage for a date range and estimate the bill for it.
+#[tracing::instrument(level = "debug", skip(auth_token))]
+pub async fn get_bill(
+    base_url: String,
+    auth_token: SecretString,
+    site_id: String,
+    start_date: String,
+    end_date: String,
+    billing: Option<BillingConfig>,
+) -> Result<Bill> {
+    let start = parse_report_date(&start_date)?;
+    let end = parse_report_date(&end_date)?;
+    if end < start {
+        bail!(
+            "End date {} is before the start date {}",
+            end_date,
+            start_date
+        );
+    }
+    let usage = get_usage_by_date(base_url, auth_token, site_id, start_date, end_date).await?;
+    Ok(build_bill(&usage, start, end, billing.as_ref()))
+}
@@ -0,0 +1,145 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use tracing::{debug, warn};
+
+use crate::watch::{next_interval_boundary, unix_now};
+
+/// How long site details are cached for, they rarely change.
+pub const SITE_DETAILS_TTL_SECONDS: u64 = 24 * 60 * 60;
+
+/// The cache used by every RestClient, only set when caching is enabled.
+static RESPONSE_CACHE: OnceLock<ResponseCache> = OnceLock::new();
+
+/// Enum type to describe how long a response may be served from the cache.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CachePolicy {
+    /// Expire after a fixed number of seconds.
+    Seconds(u64),
+    /// Expire when the next price interval starts.
+    UntilNextInterval,
+    /// Never expire, for data that will not change such as billed usage.
+    Forever,
+}
+
+impl CachePolicy {
+    /// Unix time the response expires at, None for responses that never expire.
+    pub fn expires_at(&self, now: u64) -> Option<u64> {
+        match self {
+            CachePolicy::Seconds(seconds) => Some(now + seconds),
+            CachePolicy::UntilNextInterval => Some(next_interval_boundary(now)),
+            CachePolicy::Forever => None,
+        }
+    }
+}
+
+/// Struct type for a response stored on disk.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct CacheEntry {
+    url: String,
+    expires_at: Option<u64>,
+    body: String,
+}
+
+/// Struct type for the on-disk cache of Amber API responses.
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    pub dir: PathBuf,
+}
+
+/// Default cache directory, "$XDG_CACHE_HOME/amber-cli" falling back to "$HOME/.cache/amber-cli".
+pub fn default_cache_dir() -> Option<PathBuf> {
+    let cache_home = match env::var_os("XDG_CACHE_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".cache"),
+    };
+    Some(cache_home.join("amber-cli"))
+}
+
+/// Enable caching for every RestClient created from now on.
+pub fn enable(cache: ResponseCache) {
+    if RESPONSE_CACHE.set(cache).is_err() {
+        warn!("Response cache is already enabled");
+    }
+}
+
+/// The response cache, if caching has been enabled.
+pub fn global() -> Option<&'static ResponseCache> {
+    RESPONSE_CACHE.get()
+}
+
+impl ResponseCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Responses are keyed by URL and API token, so a cache shared by several
+    /// accounts never returns another account's data.
+    fn entry_path(&self, url: &str, auth_token: &str) -> PathBuf {
+        let mut hasher = Sha256::new();
+        hasher.update(auth_token.as_bytes());
+        hasher.update(b"\n");
+        hasher.update(url.as_bytes());
+        self.dir
+            .join(format!("{}.json", hex::encode(hasher.finalize())))
+    }
+
+    /// Return the cached body for a URL, if there is one that has not expired.
+    pub fn get(&self, url: &str, auth_token: &str) -> Option<String> {
+        let path = self.entry_path(url, auth_token);
+        let entry: CacheEntry = serde_json::from_str(&fs::read_to_string(&path).ok()?).ok()?;
+        match entry.expires_at {
+            Some(expires_at) if expires_at <= unix_now() => {
+                debug!("Cache expired for {}", url);
+                let _ = fs::remove_file(path);
+                None
+            }
+            _ => {
+                debug!("Cache hit for {}", url);
+                Some(entry.body)
+            }
+        }
+    }
+
+    /// Store a response body for a URL.
+    pub fn put(&self, url: &str, auth_token: &str, body: &str, policy: CachePolicy) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let entry = CacheEntry {
+            url: url.to_string(),
+            expires_at: policy.expires_at(unix_now()),
+            body: body.to_string(),
+        };
+        fs::write(
+            self.entry_path(url, auth_token),
+            serde_json::to_string(&entry)?,
+        )?;
+        Ok(())
+    }
+
+    /// Remove every cached response, returning how many were removed.
+    pub fn clear(&self) -> Result<usize> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(error) => return Err(error.into()),
+        };
+
+        let mut removed = 0;
+        for entry in entries {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "json")
+            {
+                fs::remove_file(path)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+}
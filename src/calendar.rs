@@ -0,0 +1,84 @@
+use iso8601_timestamp::Timestamp;
+
+/// Lines longer than this many octets are folded, as required by RFC 5545.
+const MAX_LINE_OCTETS: usize = 75;
+
+/// Struct type for a single calendar event.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CalendarEvent {
+    /// Identifier that stays the same when the event is regenerated, so subscribed
+    /// calendars update the event rather than add a duplicate.
+    pub uid: String,
+    pub start_time: Timestamp,
+    pub end_time: Timestamp,
+    pub summary: String,
+    pub description: Option<String>,
+}
+
+/// Format a timestamp as an iCalendar UTC date-time, e.g. 20231225T110000Z.
+/// Amber intervals start one second past the half hour, seconds are dropped so events line up.
+pub fn format_ics_time(timestamp: &Timestamp) -> String {
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}00Z",
+        timestamp.year(),
+        u8::from(timestamp.month()),
+        timestamp.day(),
+        timestamp.hour(),
+        timestamp.minute()
+    )
+}
+
+/// Escape a text value, backslashes, semicolons, commas and newlines must be escaped.
+fn escape_text(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Fold a content line into CRLF terminated lines of at most 75 octets,
+/// continuation lines start with a space.
+fn fold_line(line: &str) -> String {
+    let mut folded = String::new();
+    let mut octets = 0;
+    for c in line.chars() {
+        if octets + c.len_utf8() > MAX_LINE_OCTETS {
+            folded.push_str("\r\n ");
+            octets = 1;
+        }
+        folded.push(c);
+        octets += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+/// Render events as an iCalendar (.ics) file, `now` is used as the DTSTAMP of every event.
+pub fn to_ics(events: &[CalendarEvent], now: &Timestamp) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!(
+            "PRODID:-//amber-cli//amber-client {}//EN",
+            env!("CARGO_PKG_VERSION")
+        ),
+        "CALSCALE:GREGORIAN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+    ];
+    for event in events {
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}", event.uid));
+        lines.push(format!("DTSTAMP:{}", format_ics_time(now)));
+        lines.push(format!("DTSTART:{}", format_ics_time(&event.start_time)));
+        lines.push(format!("DTEND:{}", format_ics_time(&event.end_time)));
+        lines.push(format!("SUMMARY:{}", escape_text(&event.summary)));
+        if let Some(description) = &event.description {
+            lines.push(format!("DESCRIPTION:{}", escape_text(description)));
+        }
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    lines.iter().map(|line| fold_line(line)).collect()
+}
@@ -0,0 +1,237 @@
+use anyhow::{bail, Context, Result};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+use crate::app_config::{check_psk, STATES};
+use crate::get_site_data;
+use crate::rest_client::SiteDetails;
+use crate::secret::SecretString;
+
+/// Distribution networks and the state they are in, matched against the start of a site's `network`.
+const NETWORK_STATES: [(&str, &str); 14] = [
+    ("ausgrid", "nsw"),
+    ("endeavour", "nsw"),
+    ("essential", "nsw"),
+    ("evoenergy", "act"),
+    ("actewagl", "act"),
+    ("energex", "qld"),
+    ("ergon", "qld"),
+    ("sa power", "sa"),
+    ("tasnetworks", "tas"),
+    ("citipower", "vic"),
+    ("powercor", "vic"),
+    ("ausnet", "vic"),
+    ("jemena", "vic"),
+    ("united", "vic"),
+];
+
+/// Work out the state a site is in from its distribution network, e.g. "Jemena" is in "vic".
+pub fn state_for_network(network: &str) -> Option<&'static str> {
+    let network = network.to_lowercase();
+    NETWORK_STATES
+        .iter()
+        .find(|(name, _)| network.starts_with(name))
+        .map(|(_, state)| *state)
+}
+
+/// Struct type for the answers given to the wizard.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InitAnswers {
+    pub base_url: String,
+    pub token_name: String,
+    pub psk: SecretString,
+    pub site_id: String,
+    pub state: String,
+}
+
+/// Render a config file from the wizard's answers, with the optional sections left commented out.
+pub fn render_config(answers: &InitAnswers) -> Result<String> {
+    // JSON strings are valid TOML basic strings, and take care of quotes and backslashes.
+    Ok(format!(
+        r#"[amberconfig]
+# Main/base URL for Amber's API - versioned
+base_url = {}
+
+[userconfig]
+# The state or territory to get renewables data for, in short format. (Eg; vic, qld, nt, etc)
+state = {}
+# The site to query, when the account has more than one.
+site_id = {}
+
+[apitoken]
+# API token name and psk created from the dev section in your Amber account
+name = {}
+psk = {}
+
+# See config.toml.example for the optional [[webhook]], [mqtt], [influxdb], [billing] and
+# [emissions] sections.
+"#,
+        serde_json::to_string(&answers.base_url)?,
+        serde_json::to_string(&answers.state)?,
+        serde_json::to_string(&answers.site_id)?,
+        serde_json::to_string(&answers.token_name)?,
+        serde_json::to_string(answers.psk.expose_secret())?,
+    ))
+}
+
+/// Write a config file only the current user can read, creating its directory.
+pub fn write_config_file(path: &Path, contents: &str, force: bool) -> Result<()> {
+    if path.exists() && !force {
+        bail!(
+            "Config file {} already exists, use --force to replace it",
+            path.display()
+        );
+    }
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // The mode only applies to new files, so tighten up a file being replaced too.
+        if path.exists() {
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    let mut file = options
+        .open(path)
+        .with_context(|| format!("Failed to write config file {}", path.display()))?;
+    file.write_all(contents.as_bytes())?;
+    Ok(())
+}
+
+/// Print a prompt and read a trimmed line, using `default` when the line is empty.
+fn prompt<R: BufRead, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    question: &str,
+    default: Option<&str>,
+) -> Result<String> {
+    match default {
+        Some(default) => write!(output, "{} [{}]: ", question, default)?,
+        None => write!(output, "{}: ", question)?,
+    }
+    output.flush()?;
+
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        bail!("No answer given for \"{}\"", question);
+    }
+    let answer = line.trim();
+    Ok(match (answer.is_empty(), default) {
+        (true, Some(default)) => default.to_string(),
+        _ => answer.to_string(),
+    })
+}
+
+/// Prompt for the API token, read from the terminal without echoing it when `hide_token` is set.
+fn prompt_token<R: BufRead, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    hide_token: bool,
+) -> Result<String> {
+    if !hide_token {
+        return prompt(input, output, "API token (psk_...)", None);
+    }
+    write!(output, "API token (psk_..., not shown): ")?;
+    output.flush()?;
+    Ok(rpassword::read_password()?.trim().to_string())
+}
+
+/// Ask which site to use when the account has more than one.
+fn choose_site<'a, R: BufRead, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    sites: &'a [SiteDetails],
+) -> Result<&'a SiteDetails> {
+    if let [site] = sites {
+        return Ok(site);
+    }
+    writeln!(output, "The API token can see {} sites:", sites.len())?;
+    for (number, site) in sites.iter().enumerate() {
+        writeln!(
+            output,
+            "  {}) {} (NMI {}, {}, {})",
+            number + 1,
+            site.id,
+            site.nmi,
+            site.network,
+            site.status
+        )?;
+    }
+    loop {
+        let answer = prompt(input, output, "Site to use", Some("1"))?;
+        match answer.parse::<usize>() {
+            Ok(number) if (1..=sites.len()).contains(&number) => return Ok(&sites[number - 1]),
+            _ => writeln!(output, "Enter a number from 1 to {}", sites.len())?,
+        }
+    }
+}
+
+/// Function to ask for an API token, check it against "/sites" and pick a site and state.
+/// Set `hide_token` when stdin is a terminal, so the token is not shown as it is typed.
+#[tracing::instrument(level = "debug", skip(input, output))]
+pub async fn run_config_init<R: BufRead, W: Write>(
+    base_url: String,
+    input: &mut R,
+    output: &mut W,
+    hide_token: bool,
+) -> Result<InitAnswers> {
+    writeln!(
+        output,
+        "Create an API token in the developer section of your Amber account first."
+    )?;
+    let token_name = prompt(input, output, "API token name", Some("amber-cli"))?;
+    let psk = loop {
+        let psk = prompt_token(input, output, hide_token)?;
+        match check_psk(&psk) {
+            Ok(()) => break SecretString::new(psk),
+            Err(problem) => writeln!(output, "{}", problem)?,
+        }
+    };
+
+    let sites = get_site_data(base_url.clone(), psk.clone())
+        .await
+        .context("The API token did not work")?;
+    if sites.is_empty() {
+        bail!("The API token works, but there are no sites on the account");
+    }
+    let site = choose_site(input, output, &sites)?;
+
+    let state = match state_for_network(&site.network) {
+        Some(state) => {
+            writeln!(
+                output,
+                "Site {} is on {}, in {}",
+                site.id, site.network, state
+            )?;
+            state.to_string()
+        }
+        None => loop {
+            let state = prompt(
+                input,
+                output,
+                &format!("State ({})", STATES.join(", ")),
+                None,
+            )?
+            .to_lowercase();
+            if STATES.contains(&state.as_str()) {
+                break state;
+            }
+            writeln!(output, "Enter one of {}", STATES.join(", "))?;
+        },
+    };
+
+    Ok(InitAnswers {
+        base_url,
+        token_name,
+        psk,
+        site_id: site.id.clone(),
+        state,
+    })
+}
@@ -0,0 +1,135 @@
+use anyhow::{bail, Result};
+use iso8601_timestamp::Timestamp;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::bill::{parse_report_date, FEED_IN_CHANNEL};
+use crate::get_usage_by_date;
+use crate::rest_client::UsageData;
+use crate::secret::SecretString;
+use crate::usage_report::GroupBy;
+
+/// Average emissions intensity of the power used in each state's grid, in kg CO2-e per kWh.
+///
+/// These are the scope 2 factors for purchased electricity from the National Greenhouse Accounts
+/// Factors 2023 (DCCEEW), WA is the South West Interconnected System and NT is the Darwin
+/// Katherine Interconnected System. Set `[emissions]` `intensity` in the config file to use a
+/// newer or your own figure.
+const STATE_INTENSITY: [(&str, f32); 8] = [
+    ("nsw", 0.68),
+    ("act", 0.68),
+    ("qld", 0.73),
+    ("vic", 0.79),
+    ("sa", 0.25),
+    ("wa", 0.51),
+    ("tas", 0.17),
+    ("nt", 0.54),
+];
+
+/// Look up the average emissions intensity of the grid for a state, e.g. "vic".
+pub fn state_intensity(state: &str) -> Result<f32> {
+    let state = state.to_lowercase();
+    match STATE_INTENSITY.iter().find(|(name, _)| *name == state) {
+        Some((_, intensity)) => Ok(*intensity),
+        None => bail!(
+            "No emissions intensity for state {}, set intensity in the [emissions] section of the config file",
+            state
+        ),
+    }
+}
+
+/// Struct type for the estimated emissions of one group, in kg CO2-e.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EmissionsGroup {
+    pub group: String,
+    /// kWh used from the grid, across the general and controlled load channels.
+    pub import_kwh: f32,
+    /// Emissions from the non renewable share of the power used.
+    pub emissions_kg: f32,
+    /// Emissions divided by kWh used, in kg CO2-e per kWh.
+    pub emissions_per_kwh: f32,
+    pub export_kwh: f32,
+    /// Emissions avoided by exported solar displacing non renewable generation.
+    pub avoided_kg: f32,
+    /// Emissions less avoided emissions.
+    pub net_kg: f32,
+}
+
+/// Mean of the grid's renewables percentage over the distinct intervals in the usage data.
+fn average_renewables(usage: &[UsageData]) -> f32 {
+    let intervals: BTreeMap<Timestamp, f32> = usage
+        .iter()
+        .map(|record| (record.start_time, record.renewables))
+        .collect();
+    match intervals.len() {
+        0 => 0.0,
+        count => intervals.values().sum::<f32>() / count as f32,
+    }
+}
+
+/// Estimate emissions from usage. `intensity` is the grid's average kg CO2-e per kWh, each kWh
+/// used or exported is charged it scaled by how non renewable the grid was at the time compared
+/// with the average over the usage data, so power used at greener times is charged less.
+pub fn build_emissions_report(
+    usage: &[UsageData],
+    group_by: GroupBy,
+    intensity: f32,
+) -> Vec<EmissionsGroup> {
+    let average_non_renewable = 1.0 - average_renewables(usage) / 100.0;
+    let non_renewable_intensity = match average_non_renewable {
+        share if share > f32::EPSILON => intensity / share,
+        _ => 0.0,
+    };
+
+    let mut groups: BTreeMap<String, EmissionsGroup> = BTreeMap::new();
+    for record in usage {
+        let (sort_key, label) = group_by.group(record);
+        let group = groups.entry(sort_key).or_insert_with(|| EmissionsGroup {
+            group: label,
+            ..Default::default()
+        });
+        let emissions = record.kwh * (1.0 - record.renewables / 100.0) * non_renewable_intensity;
+        if record.channel_type == FEED_IN_CHANNEL {
+            group.export_kwh += record.kwh;
+            group.avoided_kg += emissions;
+        } else {
+            group.import_kwh += record.kwh;
+            group.emissions_kg += emissions;
+        }
+    }
+
+    groups
+        .into_values()
+        .map(|group| EmissionsGroup {
+            emissions_per_kwh: match group.import_kwh {
+                kwh if kwh.abs() > f32::EPSILON => group.emissions_kg / kwh,
+                _ => 0.0,
+            },
+            net_kg: group.emissions_kg - group.avoided_kg,
+            ..group
+        })
+        .collect()
+}
+
+/// Function to fetch usage for a date range and estimate the emissions by day, month...
+#[tracing::instrument(level = "debug", skip(auth_token))]
+pub async fn get_emissions_report(
+    base_url: String,
+    auth_token: SecretString,
+    site_id: String,
+    start_date: String,
+    end_date: String,
+    group_by: GroupBy,
+    intensity: f32,
+) -> Result<Vec<EmissionsGroup>> {
+    if parse_report_date(&end_date)? < parse_report_date(&start_date)? {
+        bail!(
+            "End date {} is before the start date {}",
+            end_date,
+            start_date
+        );
+    }
+    let usage = get_usage_by_date(base_url, auth_token, site_id, start_date, end_date).await?;
+    Ok(build_emissions_report(&usage, group_by, intensity))
+}
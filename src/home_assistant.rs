@@ -0,0 +1,208 @@
+use serde::Serialize;
+
+use crate::mqtt::{forecast_topic, price_topic, status_topic, MqttMessage, OFFLINE, ONLINE};
+use crate::rest_client::SiteDetails;
+
+/// Price descriptors Amber uses, exposed as the options of the descriptor enum sensor.
+pub const DESCRIPTORS: [&str; 7] = [
+    "negative",
+    "extremelyLow",
+    "veryLow",
+    "low",
+    "neutral",
+    "high",
+    "spike",
+];
+
+/// Struct type for the device every Amber entity is grouped under in Home Assistant.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DiscoveryDevice {
+    pub identifiers: Vec<String>,
+    pub name: String,
+    pub manufacturer: String,
+    pub model: String,
+}
+
+/// Struct type that matches the Home Assistant MQTT discovery config for a single entity.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DiscoveryConfig {
+    pub name: String,
+    pub unique_id: String,
+    pub object_id: String,
+    pub state_topic: String,
+    pub value_template: String,
+    pub availability_topic: String,
+    pub payload_available: String,
+    pub payload_not_available: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unit_of_measurement: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state_class: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_class: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub json_attributes_topic: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    pub device: DiscoveryDevice,
+}
+
+/// Struct type pairing an entity's config with the Home Assistant component it belongs to.
+struct Entity {
+    component: &'static str,
+    key: String,
+    name: String,
+    state_topic: String,
+    value_template: String,
+    unit_of_measurement: Option<&'static str>,
+    device_class: Option<&'static str>,
+    options: Option<Vec<String>>,
+    json_attributes_topic: Option<String>,
+    icon: &'static str,
+}
+
+/// Friendly name for the price sensor of a channel type.
+fn channel_price_name(channel_type: &str) -> String {
+    match channel_type {
+        "general" => "General price".to_string(),
+        "feedIn" => "Feed in price".to_string(),
+        "controlledLoad" => "Controlled load price".to_string(),
+        other => format!("{} price", other),
+    }
+}
+
+/// Convert a camelCase channel type into a snake_case key for entity ids.
+fn snake_case(value: &str) -> String {
+    value
+        .chars()
+        .flat_map(|c| match c.is_ascii_uppercase() {
+            true => vec!['_', c.to_ascii_lowercase()],
+            false => vec![c],
+        })
+        .collect()
+}
+
+/// Build the Home Assistant discovery messages for a site.
+/// Entities are keyed by the site's NMI and read the topics watch mode publishes to.
+pub fn discovery_messages(
+    discovery_prefix: &str,
+    topic_prefix: &str,
+    site: &SiteDetails,
+) -> Vec<MqttMessage> {
+    let node_id = format!("amber_{}", site.nmi);
+    let device = DiscoveryDevice {
+        identifiers: vec![node_id.clone()],
+        name: format!("Amber {}", site.nmi),
+        manufacturer: "Amber Electric".to_string(),
+        model: site.network.clone(),
+    };
+
+    let mut channel_types: Vec<&str> = Vec::new();
+    for channel in &site.channels {
+        if !channel_types.contains(&channel.tariff_type.as_str()) {
+            channel_types.push(&channel.tariff_type);
+        }
+    }
+
+    let mut entities: Vec<Entity> = channel_types
+        .iter()
+        .map(|channel_type| Entity {
+            component: "sensor",
+            key: format!("{}_price", snake_case(channel_type)),
+            name: channel_price_name(channel_type),
+            state_topic: price_topic(topic_prefix, &site.id, channel_type),
+            value_template: "{{ value_json.perKwh }}".to_string(),
+            unit_of_measurement: Some("c/kWh"),
+            device_class: None,
+            options: None,
+            json_attributes_topic: Some(forecast_topic(topic_prefix, &site.id, channel_type)),
+            icon: "mdi:currency-usd",
+        })
+        .collect();
+
+    let general_topic = price_topic(topic_prefix, &site.id, "general");
+    entities.push(Entity {
+        component: "sensor",
+        key: "spot_price".to_string(),
+        name: "Spot price".to_string(),
+        state_topic: general_topic.clone(),
+        value_template: "{{ value_json.spotPerKwh }}".to_string(),
+        unit_of_measurement: Some("c/kWh"),
+        device_class: None,
+        options: None,
+        json_attributes_topic: None,
+        icon: "mdi:transmission-tower",
+    });
+    entities.push(Entity {
+        component: "sensor",
+        key: "renewables".to_string(),
+        name: "Renewables".to_string(),
+        state_topic: format!("{}/{}/renewables", topic_prefix, site.id),
+        value_template: "{{ value | float }}".to_string(),
+        unit_of_measurement: Some("%"),
+        device_class: None,
+        options: None,
+        json_attributes_topic: None,
+        icon: "mdi:leaf",
+    });
+    entities.push(Entity {
+        component: "sensor",
+        key: "descriptor".to_string(),
+        name: "Price descriptor".to_string(),
+        state_topic: general_topic,
+        value_template: "{{ value_json.descriptor }}".to_string(),
+        unit_of_measurement: None,
+        device_class: Some("enum"),
+        options: Some(DESCRIPTORS.iter().map(|d| d.to_string()).collect()),
+        json_attributes_topic: None,
+        icon: "mdi:tag",
+    });
+    entities.push(Entity {
+        component: "binary_sensor",
+        key: "spike".to_string(),
+        name: "Price spike".to_string(),
+        state_topic: format!("{}/{}/spike", topic_prefix, site.id),
+        value_template: "{{ 'ON' if value in ['potential', 'spike'] else 'OFF' }}".to_string(),
+        unit_of_measurement: None,
+        device_class: None,
+        options: None,
+        json_attributes_topic: None,
+        icon: "mdi:flash-alert",
+    });
+
+    entities
+        .into_iter()
+        .map(|entity| {
+            let object_id = format!("{}_{}", node_id, entity.key);
+            let config = DiscoveryConfig {
+                name: entity.name,
+                unique_id: object_id.clone(),
+                object_id: object_id.clone(),
+                state_topic: entity.state_topic,
+                value_template: entity.value_template,
+                availability_topic: status_topic(topic_prefix),
+                payload_available: ONLINE.to_string(),
+                payload_not_available: OFFLINE.to_string(),
+                unit_of_measurement: entity.unit_of_measurement.map(str::to_string),
+                state_class: entity
+                    .unit_of_measurement
+                    .map(|_| "measurement".to_string()),
+                device_class: entity.device_class.map(str::to_string),
+                options: entity.options,
+                json_attributes_topic: entity.json_attributes_topic,
+                icon: Some(entity.icon.to_string()),
+                device: device.clone(),
+            };
+            MqttMessage {
+                topic: format!(
+                    "{}/{}/{}/{}/config",
+                    discovery_prefix, entity.component, node_id, entity.key
+                ),
+                // DiscoveryConfig only contains plain fields, so serialising can not fail.
+                payload: serde_json::to_string(&config).unwrap_or_default(),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,161 @@
+use anyhow::Result;
+use iso8601_timestamp::Timestamp;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Client;
+use std::time::Duration;
+
+use crate::app_config::InfluxConfig;
+use crate::rest_client::{Error, PriceData, RenewablesData, UsageData};
+
+/// Trait for data types that can be written as InfluxDB line protocol.
+pub trait LineProtocol {
+    /// Tag the `id` passed to `to_line` is written to, the site for site data or the state for renewables.
+    const ID_TAG: &'static str;
+
+    /// Format the record as a single line: measurement, tags, fields and a nanosecond timestamp.
+    fn to_line(&self, id: &str) -> String;
+}
+
+/// Escape a tag value, commas, equals signs and spaces must be backslash escaped.
+pub fn escape_tag(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace('=', "\\=")
+        .replace(' ', "\\ ")
+}
+
+/// Escape a string field value, which is written in double quotes.
+fn escape_field(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Nanosecond timestamp InfluxDB expects by default.
+fn nanoseconds(timestamp: &Timestamp) -> i128 {
+    timestamp
+        .duration_since(Timestamp::UNIX_EPOCH)
+        .whole_nanoseconds()
+}
+
+impl LineProtocol for PriceData {
+    const ID_TAG: &'static str = "site";
+
+    fn to_line(&self, id: &str) -> String {
+        format!(
+            "amber_price,{}={},channel={},type={},descriptor={},tariff_period={} per_kwh={},spot_per_kwh={},renewables={},duration={}i,spike_status=\"{}\",estimate={} {}",
+            Self::ID_TAG,
+            escape_tag(id),
+            escape_tag(&self.channel_type),
+            escape_tag(&self.interval_type),
+            escape_tag(&self.descriptor),
+            escape_tag(&self.tariff_information.period),
+            self.per_kwh,
+            self.spot_per_kwh,
+            self.renewables,
+            self.duration,
+            escape_field(&self.spike_status),
+            self.estimate.unwrap_or(false),
+            nanoseconds(&self.start_time)
+        )
+    }
+}
+
+impl LineProtocol for UsageData {
+    const ID_TAG: &'static str = "site";
+
+    fn to_line(&self, id: &str) -> String {
+        format!(
+            "amber_usage,{}={},channel={},channel_identifier={},type={},descriptor={},tariff_period={} kwh={},cost={},per_kwh={},spot_per_kwh={},renewables={},duration={}i,quality=\"{}\",spike_status=\"{}\" {}",
+            Self::ID_TAG,
+            escape_tag(id),
+            escape_tag(&self.channel_type),
+            escape_tag(&self.channel_identifier),
+            escape_tag(&self.price_type),
+            escape_tag(&self.descriptor),
+            escape_tag(&self.tariff_information.period),
+            self.kwh,
+            self.cost,
+            self.per_kwh,
+            self.spot_per_kwh,
+            self.renewables,
+            self.duration,
+            escape_field(&self.quality),
+            escape_field(&self.spike_status),
+            nanoseconds(&self.start_time)
+        )
+    }
+}
+
+impl LineProtocol for RenewablesData {
+    const ID_TAG: &'static str = "state";
+
+    fn to_line(&self, id: &str) -> String {
+        format!(
+            "amber_renewables,{}={},type={},descriptor={} renewables={},duration={}i {}",
+            Self::ID_TAG,
+            escape_tag(id),
+            escape_tag(&self.price_type),
+            escape_tag(&self.descriptor),
+            self.renewables,
+            self.duration,
+            nanoseconds(&self.start_time)
+        )
+    }
+}
+
+/// Format a dataset as line protocol, one line per record.
+pub fn to_line_protocol<T: LineProtocol>(data: &[T], id: &str) -> String {
+    data.iter()
+        .map(|record| record.to_line(id))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Struct type for a client writing to an InfluxDB v2 HTTP write endpoint.
+pub struct InfluxWriter {
+    pub config: InfluxConfig,
+    client: Client,
+}
+
+impl InfluxWriter {
+    pub fn new(config: InfluxConfig) -> Result<Self> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(config.timeout_seconds))
+            .build()?;
+        Ok(Self { config, client })
+    }
+
+    /// Write a dataset to the configured bucket, with nanosecond precision.
+    #[tracing::instrument(level = "debug", skip(self, data), fields(url = %self.config.url))]
+    pub async fn write<T: LineProtocol>(&self, data: &[T], id: &str) -> Result<(), Error> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let write_url = format!("{}/api/v2/write", self.config.url.trim_end_matches('/'));
+        let response = self
+            .client
+            .post(write_url)
+            .query(&[
+                ("org", self.config.org.as_str()),
+                ("bucket", self.config.bucket.as_str()),
+                ("precision", "ns"),
+            ])
+            .header(
+                "AUTHORIZATION",
+                format!("Token {}", self.config.token.expose_secret()),
+            )
+            .header(CONTENT_TYPE, "text/plain; charset=utf-8")
+            .body(to_line_protocol(data, id))
+            .send()
+            .await?;
+
+        match response.status() {
+            reqwest::StatusCode::NO_CONTENT | reqwest::StatusCode::OK => Ok(()),
+            _ => Err(Error::HttpNon200Status {
+                status_code: (response.status().to_string()),
+                body: (response.text().await)?,
+            }),
+        }
+    }
+}
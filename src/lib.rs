@@ -1,5 +1,27 @@
+pub mod accuracy;
 pub mod app_config;
+pub mod battery;
+pub mod bill;
+pub mod cache;
+pub mod calendar;
+pub mod config_init;
+pub mod emissions;
+pub mod home_assistant;
+pub mod influx;
+pub mod metrics;
+pub mod mqtt;
+pub mod notifier;
+pub mod periods;
+pub mod planner;
+pub mod proxy;
 pub mod rest_client;
+pub mod schedule;
+pub mod secret;
+pub mod solar;
+pub mod spike;
+pub mod tariff;
+pub mod usage_report;
+pub mod watch;
 
 use anyhow::Result;
 use chrono::NaiveDate;
@@ -9,10 +31,12 @@ use std::process;
 use tracing::info;
 
 use rest_client::{PriceData, RenewablesData, RestClient, SiteDetails, UsageData};
+use secret::SecretString;
+use spike::{find_forecast_spike, SpikeForecast};
 
 /// Function to get and return only the users Site ID.
 #[tracing::instrument(level = "debug", skip(auth_token))]
-pub async fn get_user_site_id(base_url: String, auth_token: String) -> Result<String> {
+pub async fn get_user_site_id(base_url: String, auth_token: SecretString) -> Result<String> {
     let user_site_data = get_site_data(base_url, auth_token).await?;
     let user_site_id = user_site_data[0].id.clone();
     Ok(user_site_id)
@@ -20,7 +44,7 @@ pub async fn get_user_site_id(base_url: String, auth_token: String) -> Result<St
 
 /// Function to get the Site data
 #[tracing::instrument(level = "debug", skip(auth_token))]
-pub async fn get_site_data(base_url: String, auth_token: String) -> Result<Vec<SiteDetails>> {
+pub async fn get_site_data(base_url: String, auth_token: SecretString) -> Result<Vec<SiteDetails>> {
     let sites_url = format!("{}/sites", base_url);
     let mut user_site_details = RestClient::new_client(sites_url, auth_token.clone());
     let user_site_data = user_site_details.get_site_data().await?;
@@ -41,13 +65,15 @@ pub async fn get_site_data(base_url: String, auth_token: String) -> Result<Vec<S
 #[tracing::instrument(level = "debug", skip(auth_token))]
 pub async fn get_prices(
     base_url: String,
-    auth_token: String,
+    auth_token: SecretString,
     site_id: String,
     window: String,
 ) -> Result<Vec<PriceData>> {
+    // Windows such as "current?next=12" already carry a query string.
+    let query_separator = if window.contains('?') { '&' } else { '?' };
     let price_url = format!(
-        "{}/sites/{}/prices/{}?&resolution=30",
-        base_url, site_id, window
+        "{}/sites/{}/prices/{}{}resolution=30",
+        base_url, site_id, window, query_separator
     );
     let mut current_price_details = RestClient::new_client(price_url, auth_token.clone());
     let current_price_data = current_price_details.get_price_data().await?;
@@ -62,7 +88,7 @@ pub async fn get_prices(
 #[tracing::instrument(level = "debug", skip(auth_token))]
 pub async fn get_usage_by_date(
     base_url: String,
-    auth_token: String,
+    auth_token: SecretString,
     site_id: String,
     start_date: String,
     end_date: String,
@@ -85,7 +111,7 @@ pub async fn get_usage_by_date(
 #[tracing::instrument(level = "debug", skip(auth_token))]
 pub async fn get_renewables(
     base_url: String,
-    auth_token: String,
+    auth_token: SecretString,
     state: String,
     window: String,
 ) -> Result<Vec<RenewablesData>> {
@@ -165,10 +191,10 @@ pub async fn write_data_as_csv_to_file(file_name: String, data: Vec<UsageData>)
 
 /// Function to return the spike status from the current price Interval.
 /// Only valid for the current Interval.
-#[tracing::instrument(level = "debug")]
+#[tracing::instrument(level = "debug", skip(auth_token))]
 pub async fn get_spike_status(
     base_url: String,
-    auth_token: String,
+    auth_token: SecretString,
     site_id: String,
 ) -> Result<String> {
     let current_price_data =
@@ -182,3 +208,24 @@ pub async fn get_spike_status(
     };
     Ok(current_spike_status)
 }
+
+/// Function to scan the next N forecast intervals for a spike on the given channel.
+/// A spike is either flagged by Amber as "potential"/"spike", or priced above `price_limit`.
+#[tracing::instrument(level = "debug", skip(auth_token))]
+pub async fn get_spike_forecast(
+    base_url: String,
+    auth_token: SecretString,
+    site_id: String,
+    intervals: u32,
+    channel_type: String,
+    price_limit: Option<f32>,
+) -> Result<Option<SpikeForecast>> {
+    let window = format!("current?next={}", intervals);
+    let forecast_price_data = get_prices(base_url, auth_token, site_id, window).await?;
+
+    Ok(find_forecast_spike(
+        &forecast_price_data,
+        &channel_type,
+        price_limit,
+    ))
+}
@@ -8,37 +8,69 @@
 
 use anyhow::{Ok, Result};
 use clap::{Parser, Subcommand};
+use iso8601_timestamp::Timestamp;
 use std::env;
-use std::path::PathBuf;
+use std::io::IsTerminal;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 
-use tracing::{debug, Instrument};
+use tracing::{debug, info, Instrument};
 use tracing_subscriber::filter::LevelFilter;
 use tracing_subscriber::fmt::format::FmtSpan;
 use tracing_subscriber::{prelude::*, EnvFilter};
 
-use amber_client::app_config::AppConfig;
+use amber_client::accuracy::{default_data_dir, forecast_accuracy, run_recorder, ForecastStore};
+use amber_client::app_config::{
+    default_config_path, env_config_path, find_config_file, store_keyring_psk, AppConfig,
+    DEFAULT_BASE_URL,
+};
+use amber_client::battery::{get_battery_simulation, BatteryOptions, Strategy};
+use amber_client::bill::get_bill;
+use amber_client::cache::{self, default_cache_dir, ResponseCache};
+use amber_client::calendar::{to_ics, CalendarEvent};
+use amber_client::config_init::{render_config, run_config_init, write_config_file};
+use amber_client::emissions::{get_emissions_report, state_intensity};
+use amber_client::influx::{to_line_protocol, InfluxWriter, LineProtocol};
+use amber_client::metrics::serve_metrics;
+use amber_client::notifier::Notifiers;
+use amber_client::periods::{find_price_periods, PeriodThresholds};
+use amber_client::planner::{parse_duration_minutes, plan_cheapest, Objective, PlanOptions};
+use amber_client::proxy::serve_proxy;
+use amber_client::schedule::{plan_schedule, LoadsFile};
+use amber_client::secret::SecretString;
+use amber_client::solar::get_solar_report;
+use amber_client::tariff::{compare_tariffs, TariffsFile};
+use amber_client::usage_report::{get_renewables_report, get_usage_report, GroupBy};
+use amber_client::watch::{run_watch, WatchOptions};
 use amber_client::{
-    get_prices, get_renewables, get_site_data, get_spike_status, get_usage_by_date,
-    get_user_site_id, write_data_as_csv_to_file,
+    get_prices, get_renewables, get_site_data, get_spike_forecast, get_spike_status,
+    get_usage_by_date, get_user_site_id, write_data_as_csv_to_file,
 };
 
-use serde_yaml;
 // Main CLI options
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
-    /// Full path to config.toml file.
+    /// Full path to config.toml file, defaults to $XDG_CONFIG_HOME/amber-cli/config.toml.
     #[arg(short, long, value_name = "config.toml")]
-    config_file: PathBuf,
+    config_file: Option<PathBuf>,
+
+    /// Profile to use from the [profiles.<name>] sections of the config file.
+    #[arg(short, long)]
+    profile: Option<String>,
 
     /// Enable debug logging, defaults to off.
     #[arg(short, long, default_missing_value("true"), default_value("false"))]
     debug: bool,
 
-    /// Output format: json or yaml
+    /// Output format: json, yaml, csv (reports), influx (line protocol) or ics (price forecast)
     #[arg(short, long, default_value = "json")]
     format: String,
 
+    /// Always query the Amber API, without reading or writing the response cache.
+    #[arg(long)]
+    no_cache: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -49,13 +81,315 @@ enum Commands {
     /// Display details about your site.
     SiteDetails,
     #[command(subcommand)]
-    Price(Window),
+    Price(PriceWindow),
     #[command(subcommand)]
     Usage(Dates),
     #[command(subcommand)]
     Renewables(Window),
-    /// Current interval's spike status.
-    Spike,
+    /// Current interval's spike status, or scan the forecast for upcoming spikes.
+    Spike {
+        #[command(subcommand)]
+        command: Option<SpikeCommand>,
+    },
+    /// Poll Amber every interval and alert on current and forecast spikes.
+    Watch {
+        /// Number of forecast intervals to scan for spikes.
+        #[arg(long, default_value_t = 12)]
+        forecast_intervals: u32,
+        /// Channel to watch (general, controlledLoad, feedIn).
+        #[arg(long, default_value = "general")]
+        channel: String,
+        /// Also alert when the price goes above this limit in c/kWh.
+        #[arg(long)]
+        price_limit: Option<f32>,
+        /// Poll once and exit, instead of running until stopped.
+        #[arg(long)]
+        once: bool,
+    },
+    /// Serve Prometheus metrics, refreshed from Amber once per interval.
+    ServeMetrics {
+        /// Address and port to serve "/metrics" on.
+        #[arg(long, default_value = "0.0.0.0:9860")]
+        listen: SocketAddr,
+        /// Number of forecast intervals used for the forecast min/max gauges.
+        #[arg(long, default_value_t = 12)]
+        forecast_intervals: u32,
+    },
+    #[command(subcommand)]
+    Cache(CacheCommand),
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    #[command(subcommand)]
+    Profiles(ProfilesCommand),
+    #[command(subcommand)]
+    Plan(PlanCommand),
+    #[command(subcommand)]
+    Report(ReportCommand),
+    #[command(subcommand)]
+    Simulate(SimulateCommand),
+    #[command(subcommand)]
+    Accuracy(AccuracyCommand),
+    /// Serve a local copy of the Amber API, cached once per interval, for other devices to query.
+    Serve {
+        /// Address and port to serve the API on, use 0.0.0.0:8480 to serve your LAN.
+        #[arg(long, default_value = "127.0.0.1:8480")]
+        listen: SocketAddr,
+    },
+}
+
+/// Spike early warning options
+#[derive(Subcommand, Debug)]
+enum SpikeCommand {
+    /// Scan the next forecast intervals for the first expected spike.
+    Forecast {
+        /// Number of forecast intervals to scan.
+        #[arg(long, default_value_t = 12)]
+        intervals: u32,
+        /// Channel to scan (general, controlledLoad, feedIn).
+        #[arg(long, default_value = "general")]
+        channel: String,
+        /// Treat intervals priced above this limit in c/kWh as a spike.
+        #[arg(long)]
+        price_limit: Option<f32>,
+    },
+}
+
+/// Manage the config file
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// Validate the config file and test the API token against Amber.
+    Check,
+    /// Ask for an API token, check it works and write a new config file.
+    Init {
+        /// Replace the config file if it already exists.
+        #[arg(long)]
+        force: bool,
+    },
+    /// Read an API token from stdin and store it in the OS secret service, for `keyring = true`.
+    StoreToken {
+        /// Name of the API token, the `name` in the [apitoken] section.
+        name: String,
+    },
+}
+
+/// Profiles for more than one account in the config file
+#[derive(Subcommand, Debug)]
+enum ProfilesCommand {
+    /// List the profiles in the config file.
+    List,
+}
+
+/// Plan when to run loads using the price forecast
+#[derive(Subcommand, Debug)]
+enum PlanCommand {
+    /// Find the cheapest time to run a load in the forecast.
+    Cheapest {
+        /// How long the load runs for, e.g. 2h, 90m or 1h30m.
+        #[arg(long, value_parser = parse_duration_minutes)]
+        duration: u32,
+        /// How far ahead the load has to run, e.g. 12h.
+        #[arg(long, value_parser = parse_duration_minutes, default_value = "12h")]
+        within: u32,
+        /// Channel to plan for (general, controlledLoad).
+        #[arg(long, default_value = "general")]
+        channel: String,
+        /// Run the load in one go, the default.
+        #[arg(long, conflicts_with = "split")]
+        contiguous: bool,
+        /// Split the load over the best intervals instead of running it in one go.
+        #[arg(long)]
+        split: bool,
+        /// Optimise for the lowest price, the highest renewables percentage or a weighting of both.
+        #[arg(long, value_enum, default_value_t = Objective::Price)]
+        optimise: Objective,
+        /// How many c/kWh each percentage point of renewables is worth, with --optimise weighted.
+        #[arg(long, default_value_t = 0.2)]
+        carbon_weight: f32,
+        /// Power the load draws in kW, to estimate its cost in cents.
+        #[arg(long)]
+        power: Option<f32>,
+    },
+    /// Find the time with the highest renewables percentage in the grid to run a load.
+    Greenest {
+        /// How long the load runs for, e.g. 2h, 90m or 1h30m.
+        #[arg(long, value_parser = parse_duration_minutes)]
+        duration: u32,
+        /// How far ahead the load has to run, e.g. 12h.
+        #[arg(long, value_parser = parse_duration_minutes, default_value = "12h")]
+        within: u32,
+        /// Channel to plan for (general, controlledLoad).
+        #[arg(long, default_value = "general")]
+        channel: String,
+        /// Run the load in one go, the default.
+        #[arg(long, conflicts_with = "split")]
+        contiguous: bool,
+        /// Split the load over the best intervals instead of running it in one go.
+        #[arg(long)]
+        split: bool,
+        /// Power the load draws in kW, to estimate its cost in cents.
+        #[arg(long)]
+        power: Option<f32>,
+    },
+    /// Schedule the flexible loads in a TOML file at the lowest total cost.
+    Schedule {
+        /// Path to the TOML file listing the loads, see loads.toml.example.
+        #[arg(long, value_name = "loads.toml")]
+        loads: PathBuf,
+        /// How far ahead of now to schedule, e.g. 24h.
+        #[arg(long, value_parser = parse_duration_minutes, default_value = "24h")]
+        within: u32,
+        /// Channel to plan for (general, controlledLoad).
+        #[arg(long, default_value = "general")]
+        channel: String,
+        /// [Optional] Also write the schedule to an iCalendar (.ics) file.
+        #[arg(long, value_name = "FILE")]
+        ics: Option<PathBuf>,
+    },
+}
+
+/// Reports built from historical usage data
+#[derive(Subcommand, Debug)]
+enum ReportCommand {
+    /// Estimate the bill for a date range, with a breakdown per channel, tariff period and day.
+    Bill {
+        /// Start date to report from (yyyy-mm-dd).
+        start_date: String,
+        /// End date to report to, inclusive (yyyy-mm-dd).
+        end_date: String,
+    },
+    /// Roll usage up per channel by day, week, month, hour of day or weekday.
+    Usage {
+        /// Start date to report from (yyyy-mm-dd).
+        start_date: String,
+        /// End date to report to, inclusive (yyyy-mm-dd).
+        end_date: String,
+        /// How to group the usage intervals.
+        #[arg(long, value_enum, default_value_t = GroupBy::Day)]
+        group_by: GroupBy,
+    },
+    /// Report how much of the power you used came from renewables, compared to the grid average.
+    Renewables {
+        /// Start date to report from (yyyy-mm-dd).
+        start_date: String,
+        /// End date to report to, inclusive (yyyy-mm-dd).
+        end_date: String,
+        /// How to group the usage intervals.
+        #[arg(long, value_enum, default_value_t = GroupBy::Month)]
+        group_by: GroupBy,
+    },
+    /// Estimate the kg CO2-e of the power you used, and avoided by exporting solar.
+    Emissions {
+        /// Start date to report from (yyyy-mm-dd).
+        start_date: String,
+        /// End date to report to, inclusive (yyyy-mm-dd).
+        end_date: String,
+        /// How to group the usage intervals.
+        #[arg(long, value_enum, default_value_t = GroupBy::Month)]
+        group_by: GroupBy,
+    },
+    /// Report solar export earnings, and the intervals where exporting cost money.
+    Solar {
+        /// Start date to report from (yyyy-mm-dd).
+        start_date: String,
+        /// End date to report to, inclusive (yyyy-mm-dd).
+        end_date: String,
+    },
+    /// Compare what your usage would have cost on the retail plans in a TOML file, per month.
+    Compare {
+        /// Start date to report from (yyyy-mm-dd).
+        start_date: String,
+        /// End date to report to, inclusive (yyyy-mm-dd).
+        end_date: String,
+        /// Path to the TOML file describing the plans, see tariffs.toml.example.
+        #[arg(long, value_name = "tariffs.toml")]
+        tariffs: PathBuf,
+    },
+}
+
+/// Replay historical usage and prices with changes to your site
+#[derive(Subcommand, Debug)]
+enum SimulateCommand {
+    /// Replay usage with a home battery, and report the savings, cycles and payback.
+    Battery {
+        /// Start date to replay from (yyyy-mm-dd).
+        start_date: String,
+        /// End date to replay to, inclusive (yyyy-mm-dd).
+        end_date: String,
+        /// Usable capacity in kWh.
+        #[arg(long, default_value_t = 13.5)]
+        capacity: f32,
+        /// Maximum charge and discharge power in kW.
+        #[arg(long, default_value_t = 5.0)]
+        power: f32,
+        /// Round trip efficiency, between 0 and 1.
+        #[arg(long, default_value_t = 0.9)]
+        efficiency: f32,
+        /// When the battery charges and discharges.
+        #[arg(long, value_enum, default_value_t = Strategy::SelfConsumption)]
+        strategy: Strategy,
+        /// Charge from the grid at or below this price in c/kWh (arbitrage and spike-export).
+        #[arg(long, default_value_t = 10.0)]
+        charge_below: f32,
+        /// Discharge at or above this price in c/kWh (arbitrage).
+        #[arg(long, default_value_t = 30.0)]
+        discharge_above: f32,
+        /// Installed cost of the battery in dollars, to work out the payback period.
+        #[arg(long)]
+        cost: Option<f32>,
+    },
+}
+
+/// Track how accurate the price forecast is
+#[derive(Subcommand, Debug)]
+enum AccuracyCommand {
+    /// Snapshot the forecast every interval into the local store.
+    Record {
+        /// Number of forecast intervals to snapshot.
+        #[arg(long, default_value_t = 48)]
+        forecast_intervals: u32,
+        /// Record once and exit, for running from cron.
+        #[arg(long)]
+        once: bool,
+    },
+    /// Compare the recorded forecasts with the actual prices, by how far ahead they were made.
+    Report {
+        /// Channel to report on (general, controlledLoad, feedIn).
+        #[arg(long, default_value = "general")]
+        channel: String,
+    },
+}
+
+/// Manage the on-disk cache of API responses
+#[derive(Subcommand, Debug)]
+enum CacheCommand {
+    /// Remove every cached response.
+    Clear,
+}
+
+/// Price window to query for data (current, next, previous, forecast)
+#[derive(Parser, Debug)]
+enum PriceWindow {
+    /// Current interval data.
+    Current,
+    /// Previous interval data.
+    Previous,
+    /// Forecast interval data.
+    Next,
+    /// Forecast intervals, with `--format ics` as calendar events for cheap and spike periods.
+    Forecast {
+        /// Number of forecast intervals to query.
+        #[arg(long, default_value_t = 48)]
+        intervals: u32,
+        /// Channel for the calendar events (general, controlledLoad, feedIn).
+        #[arg(long, default_value = "general")]
+        channel: String,
+        /// Mark intervals priced below this limit in c/kWh as cheap, instead of using Amber's descriptor.
+        #[arg(long)]
+        cheap_below: Option<f32>,
+        /// Mark intervals priced above this limit in c/kWh as a spike, instead of using Amber's descriptor.
+        #[arg(long)]
+        spike_above: Option<f32>,
+    },
 }
 
 /// Price window to query for data (current, next, previous)
@@ -80,6 +414,9 @@ enum Dates {
         end_date: String,
         /// [Optional] Path to save/export data in CSV format.
         filename_to_export_to: Option<PathBuf>,
+        /// Also write the usage data to the InfluxDB server in the config file.
+        #[arg(long)]
+        write_influx: bool,
     },
 }
 
@@ -118,25 +455,22 @@ async fn main() -> Result<()> {
     // For now if the "-d / --debug" flag is present/true then just overwrite the "RUST_LOG".
     // This will overwrite anything the user as set for this env_var.
     // Print warning via println as tracing_subscriber is not Initializing yet.
-    match cli_args.debug {
-        true => {
-            println!("WARNING!");
-            println!(
-                "WARNING!{:>25} mode overrides the RUST_LOG environmental variable!",
-                "DEBUG"
-            );
-            println!(
-                "WARNING!{0:>25} will be set as the RUST_LOG environmental variable.",
-                "DEBUG"
-            );
-            env::set_var("RUST_LOG", "DEBUG");
-            println!(
-                "WARNING!{0:>28} environmental variable has been set.",
-                "RUST_LOG"
-            );
-            println!("WARNING!");
-        }
-        false => (),
+    if cli_args.debug {
+        println!("WARNING!");
+        println!(
+            "WARNING!{:>25} mode overrides the RUST_LOG environmental variable!",
+            "DEBUG"
+        );
+        println!(
+            "WARNING!{0:>25} will be set as the RUST_LOG environmental variable.",
+            "DEBUG"
+        );
+        env::set_var("RUST_LOG", "DEBUG");
+        println!(
+            "WARNING!{0:>28} environmental variable has been set.",
+            "RUST_LOG"
+        );
+        println!("WARNING!");
     }
 
     let logging_filter = EnvFilter::builder()
@@ -168,78 +502,181 @@ async fn main() -> Result<()> {
     // Due to skipping the 'api_token' field on configured instruments.
     debug!("Debug traces/spans will not print your API key. Due to purposefully skipping the 'api_token' field.");
 
-    // map the CLI argument of "config_file: PathBuf" to a string, using lossy conversion
-    // making this is safe to use with non uni-code data.
-    // https://doc.rust-lang.org/std/path/struct.Path.html#method.display
-    let app_config_file = cli_args.config_file.display().to_string();
+    // Site details, prices and billed usage are cached on disk between runs, see src/cache.rs.
+    let response_cache = default_cache_dir().map(ResponseCache::new);
+    if let Commands::Cache(CacheCommand::Clear) = cli_args.command {
+        match response_cache {
+            Some(response_cache) => {
+                let removed = response_cache.clear()?;
+                println!(
+                    "Removed {} cached responses from {}",
+                    removed,
+                    response_cache.dir.display()
+                );
+            }
+            None => println!("No cache directory, neither XDG_CACHE_HOME or HOME are set"),
+        }
+        return Ok(());
+    }
+
+    // Find the config file, without one the config is read from AMBER_* environment variables.
+    // map the path to a string, using lossy conversion making this is safe to use with non
+    // uni-code data. https://doc.rust-lang.org/std/path/struct.Path.html#method.display
+    let app_config_file = find_config_file(cli_args.config_file.clone())
+        .map(|config_path| config_path.display().to_string());
+    match cli_args.command {
+        Commands::Config(ConfigCommand::Check) => {
+            return check_config(app_config_file.as_deref(), cli_args.profile.as_deref()).await
+        }
+        Commands::Profiles(ProfilesCommand::List) => {
+            let profiles = AppConfig::profiles(app_config_file.as_deref())?;
+            return print_rows(&profiles, &output_format);
+        }
+        Commands::Config(ConfigCommand::Init { force }) => {
+            let config_path = match cli_args
+                .config_file
+                .clone()
+                .or_else(env_config_path)
+                .or_else(default_config_path)
+            {
+                Some(config_path) => config_path,
+                None => anyhow::bail!(
+                    "No --config-file given, and neither AMBER_CONFIG, XDG_CONFIG_HOME or HOME are set"
+                ),
+            };
+            return init_config(&config_path, force).await;
+        }
+        Commands::Config(ConfigCommand::StoreToken { name }) => {
+            print!("API token (psk_...): ");
+            std::io::Write::flush(&mut std::io::stdout())?;
+            let mut psk = String::new();
+            std::io::stdin().read_line(&mut psk)?;
+            store_keyring_psk(&name, &SecretString::from(psk.trim()))?;
+            println!("Stored API token {} in the OS secret service", name);
+            return Ok(());
+        }
+        _ => (),
+    }
+
+    // Commands that poll every interval want fresh data, and `serve` keeps its own cache.
+    let long_running = matches!(
+        cli_args.command,
+        Commands::Watch { .. }
+            | Commands::ServeMetrics { .. }
+            | Commands::Serve { .. }
+            | Commands::Accuracy(AccuracyCommand::Record { .. })
+    );
+    match response_cache {
+        Some(response_cache) if !cli_args.no_cache && !long_running => {
+            cache::enable(response_cache)
+        }
+        _ => debug!("Response cache disabled"),
+    }
 
     // read config file
-    let config = AppConfig::get(app_config_file.clone())
+    let config = AppConfig::get(app_config_file.clone(), cli_args.profile.as_deref())
         .instrument(tracing::debug_span!(
             "Load config file",
-            "File={}",
-            app_config_file
+            "File={:?} Profile={:?}",
+            app_config_file,
+            cli_args.profile
         ))
         .await?;
 
     // map API token, Amber url and users state from config
-    let auth_token = config.apitoken.psk;
-    let base_url = config.amberconfig.base_url;
-    let users_state = config.userconfig.state;
+    let auth_token = config.apitoken.psk.clone();
+    let base_url = config.amberconfig.base_url.clone();
+    let users_state = config.userconfig.state.clone();
 
     // Get the Site ID first, so tha`t we can reuse it later without an additonal API call.
-    let site_id = get_user_site_id(base_url.clone(), auth_token.clone()).await?;
+    let site_id = match config.userconfig.site_id.clone() {
+        Some(site_id) => site_id,
+        None => get_user_site_id(base_url.clone(), auth_token.clone()).await?,
+    };
 
     match cli_args.command {
-        Commands::Price(Window::Current) => {
+        Commands::Price(PriceWindow::Current) => {
             let _window = "current".to_string();
-            let current_price_data = get_prices(base_url, auth_token, site_id, _window).await?;
+            let current_price_data =
+                get_prices(base_url, auth_token, site_id.clone(), _window).await?;
             // let current_price_data_json = serde_json::to_string(&current_price_data)?;
-            print_output(&current_price_data, &output_format)?;
+            print_series(&current_price_data, &site_id, &output_format)?;
             // println!("{}", current_price_data_json);
         }
-        Commands::Price(Window::Previous) => {
+        Commands::Price(PriceWindow::Previous) => {
             let _window = "current?previous=1".to_string();
-            let current_price_data = get_prices(base_url, auth_token, site_id, _window).await?;
+            let current_price_data =
+                get_prices(base_url, auth_token, site_id.clone(), _window).await?;
             // let current_price_data_json = serde_json::to_string(&current_price_data)?;
-            print_output(&current_price_data, &output_format)?;
+            print_series(&current_price_data, &site_id, &output_format)?;
             // println!("{}", current_price_data_json);
-
         }
 
-        Commands::Price(Window::Next) => {
+        Commands::Price(PriceWindow::Next) => {
             let _window = "current?next=1".to_string();
-            let current_price_data = get_prices(base_url, auth_token, site_id, _window).await?;
+            let current_price_data =
+                get_prices(base_url, auth_token, site_id.clone(), _window).await?;
             // let current_price_data_json = serde_json::to_string(&current_price_data)?;
-            print_output(&current_price_data, &output_format)?;
+            print_series(&current_price_data, &site_id, &output_format)?;
             // println!("{}", current_price_data_json);
         }
 
+        Commands::Price(PriceWindow::Forecast {
+            intervals,
+            channel,
+            cheap_below,
+            spike_above,
+        }) => {
+            let _window = format!("current?next={}", intervals);
+            let forecast_price_data =
+                get_prices(base_url, auth_token, site_id.clone(), _window).await?;
+            if output_format == "ics" {
+                let thresholds = PeriodThresholds {
+                    cheap_below,
+                    spike_above,
+                };
+                let events: Vec<CalendarEvent> =
+                    find_price_periods(&forecast_price_data, &channel, &thresholds)
+                        .iter()
+                        .map(|period| period.calendar_event())
+                        .collect();
+                print!("{}", to_ics(&events, &Timestamp::now_utc()));
+            } else {
+                print_series(&forecast_price_data, &site_id, &output_format)?;
+            }
+        }
+
         Commands::Renewables(Window::Current) => {
             let _window = "current".to_string();
             let renewables_percent_in_grid_data =
-                get_renewables(base_url, auth_token, users_state, _window).await?;
-            let renewables_percent_in_grid_json =
-                serde_json::to_string(&renewables_percent_in_grid_data)?;
-            println!("{}", renewables_percent_in_grid_json);
+                get_renewables(base_url, auth_token, users_state.clone(), _window).await?;
+            print_series(
+                &renewables_percent_in_grid_data,
+                &users_state,
+                &output_format,
+            )?;
         }
 
         Commands::Renewables(Window::Previous) => {
             let _window = "current?previous=1".to_string();
             let renewables_percent_in_grid_data =
-                get_renewables(base_url, auth_token, users_state, _window).await?;
-            let renewables_percent_in_grid_json =
-                serde_json::to_string(&renewables_percent_in_grid_data)?;
-            println!("{}", renewables_percent_in_grid_json);
+                get_renewables(base_url, auth_token, users_state.clone(), _window).await?;
+            print_series(
+                &renewables_percent_in_grid_data,
+                &users_state,
+                &output_format,
+            )?;
         }
 
         Commands::Renewables(Window::Next) => {
             let _window = "current?next=1".to_string();
             let renewables_percent_in_grid_data =
-                get_renewables(base_url, auth_token, users_state, _window).await?;
-            let renewables_percent_in_grid_json =
-                serde_json::to_string(&renewables_percent_in_grid_data)?;
-            println!("{}", renewables_percent_in_grid_json);
+                get_renewables(base_url, auth_token, users_state.clone(), _window).await?;
+            print_series(
+                &renewables_percent_in_grid_data,
+                &users_state,
+                &output_format,
+            )?;
         }
 
         Commands::SiteDetails => {
@@ -248,18 +685,288 @@ async fn main() -> Result<()> {
             println!("{}", site_data_json);
         }
 
-        Commands::Spike => {
+        Commands::Spike { command: None } => {
             let current_spike_status = get_spike_status(base_url, auth_token, site_id).await?;
             println!("{}", current_spike_status);
         }
 
+        Commands::Spike {
+            command:
+                Some(SpikeCommand::Forecast {
+                    intervals,
+                    channel,
+                    price_limit,
+                }),
+        } => {
+            let spike_forecast = get_spike_forecast(
+                base_url,
+                auth_token,
+                site_id,
+                intervals,
+                channel,
+                price_limit,
+            )
+            .await?;
+            match spike_forecast {
+                Some(spike) => println!("{}", spike.summary()),
+                None => println!("No spike forecast in the next {} intervals", intervals),
+            }
+        }
+
+        Commands::Watch {
+            forecast_intervals,
+            channel,
+            price_limit,
+            once,
+        } => {
+            let options = WatchOptions {
+                forecast_intervals,
+                channel_type: channel,
+                price_limit,
+                once,
+            };
+            let notifiers = Notifiers::from_config(&config).await?;
+            if let Some(mqtt) = notifiers.mqtt.as_ref().filter(|mqtt| mqtt.config.discovery) {
+                let site_data = get_site_data(base_url.clone(), auth_token.clone()).await?;
+                for site in site_data.iter().filter(|site| site.id == site_id) {
+                    mqtt.publish_discovery(site).await?;
+                }
+            }
+            run_watch(base_url, auth_token, site_id, options, &notifiers).await?;
+        }
+
+        Commands::ServeMetrics {
+            listen,
+            forecast_intervals,
+        } => {
+            serve_metrics(base_url, auth_token, site_id, listen, forecast_intervals).await?;
+        }
+
+        Commands::Plan(PlanCommand::Cheapest {
+            duration,
+            within,
+            channel,
+            contiguous: _,
+            split,
+            optimise,
+            carbon_weight,
+            power,
+        }) => {
+            let options = PlanOptions {
+                duration_minutes: duration,
+                within_minutes: within,
+                channel_type: channel,
+                contiguous: !split,
+                objective: optimise,
+                carbon_weight,
+                power_kw: power,
+            };
+            let load_plan = plan_cheapest(base_url, auth_token, site_id, options).await?;
+            print_output(&load_plan, &output_format)?;
+        }
+
+        Commands::Plan(PlanCommand::Greenest {
+            duration,
+            within,
+            channel,
+            contiguous: _,
+            split,
+            power,
+        }) => {
+            let options = PlanOptions {
+                duration_minutes: duration,
+                within_minutes: within,
+                channel_type: channel,
+                contiguous: !split,
+                objective: Objective::Renewables,
+                carbon_weight: 0.0,
+                power_kw: power,
+            };
+            let load_plan = plan_cheapest(base_url, auth_token, site_id, options).await?;
+            print_output(&load_plan, &output_format)?;
+        }
+
+        Commands::Plan(PlanCommand::Schedule {
+            loads,
+            within,
+            channel,
+            ics,
+        }) => {
+            let loads_file = LoadsFile::read(&loads.display().to_string())?;
+            let load_schedule =
+                plan_schedule(base_url, auth_token, site_id, channel, within, loads_file).await?;
+            if let Some(ics_file) = ics {
+                let calendar = to_ics(&load_schedule.calendar_events(), &Timestamp::now_utc());
+                std::fs::write(&ics_file, calendar)?;
+                info!("Wrote the schedule to {}", ics_file.display());
+            }
+            print_output(&load_schedule, &output_format)?;
+        }
+
+        Commands::Report(ReportCommand::Bill {
+            start_date,
+            end_date,
+        }) => {
+            let bill = get_bill(
+                base_url,
+                auth_token,
+                site_id,
+                start_date,
+                end_date,
+                config.billing.clone(),
+            )
+            .await?;
+            print_output(&bill, &output_format)?;
+        }
+
+        Commands::Report(ReportCommand::Usage {
+            start_date,
+            end_date,
+            group_by,
+        }) => {
+            let usage_report = get_usage_report(
+                base_url, auth_token, site_id, start_date, end_date, group_by,
+            )
+            .await?;
+            print_rows(&usage_report, &output_format)?;
+        }
+
+        Commands::Report(ReportCommand::Renewables {
+            start_date,
+            end_date,
+            group_by,
+        }) => {
+            let renewables_report = get_renewables_report(
+                base_url, auth_token, site_id, start_date, end_date, group_by,
+            )
+            .await?;
+            print_rows(&renewables_report, &output_format)?;
+        }
+
+        Commands::Report(ReportCommand::Emissions {
+            start_date,
+            end_date,
+            group_by,
+        }) => {
+            let intensity = match &config.emissions {
+                Some(emissions) => emissions.intensity,
+                None => state_intensity(&users_state)?,
+            };
+            let emissions_report = get_emissions_report(
+                base_url, auth_token, site_id, start_date, end_date, group_by, intensity,
+            )
+            .await?;
+            print_rows(&emissions_report, &output_format)?;
+        }
+
+        Commands::Report(ReportCommand::Solar {
+            start_date,
+            end_date,
+        }) => {
+            let solar_report =
+                get_solar_report(base_url, auth_token, site_id, start_date, end_date).await?;
+            print_output(&solar_report, &output_format)?;
+        }
+
+        Commands::Report(ReportCommand::Compare {
+            start_date,
+            end_date,
+            tariffs,
+        }) => {
+            let tariffs_file = TariffsFile::read(&tariffs.display().to_string())?;
+            let comparison = compare_tariffs(
+                base_url,
+                auth_token,
+                site_id,
+                start_date,
+                end_date,
+                tariffs_file,
+                config.billing.clone(),
+            )
+            .await?;
+            print_output(&comparison, &output_format)?;
+        }
+
+        Commands::Simulate(SimulateCommand::Battery {
+            start_date,
+            end_date,
+            capacity,
+            power,
+            efficiency,
+            strategy,
+            charge_below,
+            discharge_above,
+            cost,
+        }) => {
+            let options = BatteryOptions {
+                capacity_kwh: capacity,
+                power_kw: power,
+                efficiency,
+                strategy,
+                charge_below,
+                discharge_above,
+                battery_cost: cost,
+            };
+            let simulation = get_battery_simulation(
+                base_url, auth_token, site_id, start_date, end_date, options,
+            )
+            .await?;
+            print_output(&simulation, &output_format)?;
+        }
+
+        Commands::Accuracy(command) => {
+            let store = default_data_dir().map(ForecastStore::new).ok_or_else(|| {
+                anyhow::anyhow!("No data directory, neither XDG_DATA_HOME or HOME are set")
+            })?;
+            match command {
+                AccuracyCommand::Record {
+                    forecast_intervals,
+                    once,
+                } => {
+                    run_recorder(
+                        base_url,
+                        auth_token,
+                        site_id,
+                        forecast_intervals,
+                        once,
+                        &store,
+                    )
+                    .await?;
+                }
+                AccuracyCommand::Report { channel } => {
+                    let stored = store.load(&site_id)?;
+                    print_rows(&forecast_accuracy(&stored, &channel), &output_format)?;
+                }
+            }
+        }
+
+        // Handled before the config file is loaded.
+        Commands::Cache(CacheCommand::Clear) => (),
+        Commands::Config(_) | Commands::Profiles(_) => (),
+
+        Commands::Serve { listen } => {
+            serve_proxy(base_url, auth_token, site_id, users_state, listen).await?;
+        }
+
         Commands::Usage(Dates::DateRange {
             start_date,
             end_date,
             filename_to_export_to,
+            write_influx,
         }) => {
             let usage =
-                get_usage_by_date(base_url, auth_token, site_id, start_date, end_date).await?;
+                get_usage_by_date(base_url, auth_token, site_id.clone(), start_date, end_date)
+                    .await?;
+
+            if write_influx {
+                let influx_config = config.influxdb.clone().ok_or_else(|| {
+                    anyhow::anyhow!("--write-influx needs an [influxdb] section in the config file")
+                })?;
+                InfluxWriter::new(influx_config)?
+                    .write(&usage, &site_id)
+                    .await?;
+                info!("Wrote {} usage records to InfluxDB", usage.len());
+            }
 
             // If the Option<path> contains a value then we enter export/save to file mode.
             // Otherwise None will fall back to print to stdout as normal.
@@ -270,8 +977,7 @@ async fn main() -> Result<()> {
                     //println!("file: {:?}", filename);
                 }
                 None => {
-                    let usage_json = serde_json::to_string(&usage)?;
-                    println!("{}", usage_json);
+                    print_series(&usage, &site_id, &output_format)?;
                 }
             }
         }
@@ -280,17 +986,108 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
-// The print_output function is only used by the price command
+// Validate the config file, then test the API token with a live, uncached, call to "/sites".
+async fn check_config(app_config_file: Option<&str>, profile: Option<&str>) -> Result<()> {
+    let config = AppConfig::load(app_config_file, profile)?;
+    let mut config_source = match app_config_file {
+        Some(app_config_file) => format!("Config file {}", app_config_file),
+        None => "Config from AMBER_* environment variables".to_string(),
+    };
+    if let Some(profile) = profile {
+        config_source = format!("{} (profile {})", config_source, profile);
+    }
+    let problems = config.problems();
+    if !problems.is_empty() {
+        for problem in &problems {
+            println!("ERROR: {}", problem);
+        }
+        anyhow::bail!("Found {} problems in {}", problems.len(), config_source);
+    }
+    println!("{} is valid", config_source);
+
+    let sites = get_site_data(
+        config.amberconfig.base_url.clone(),
+        config.apitoken.psk.clone(),
+    )
+    .await
+    .map_err(|error| anyhow::anyhow!("API token test failed: {}", error))?;
+    println!(
+        "API token \"{}\" works, it can see {} site(s)",
+        config.apitoken.name,
+        sites.len()
+    );
+    for site in sites {
+        println!("  {} (NMI {}, {})", site.id, site.nmi, site.status);
+    }
+    Ok(())
+}
+
+// Ask for an API token and write a new config file, only readable by the current user.
+async fn init_config(config_path: &Path, force: bool) -> Result<()> {
+    if config_path.exists() && !force {
+        anyhow::bail!(
+            "Config file {} already exists, use --force to replace it",
+            config_path.display()
+        );
+    }
+    let stdin = std::io::stdin();
+    let hide_token = stdin.is_terminal();
+    let answers = run_config_init(
+        DEFAULT_BASE_URL.to_string(),
+        &mut stdin.lock(),
+        &mut std::io::stdout(),
+        hide_token,
+    )
+    .await?;
+    write_config_file(config_path, &render_config(&answers)?, force)?;
+    println!("Wrote config file {}", config_path.display());
+    Ok(())
+}
+
+// The print_output function is used by commands that support the --format option
 fn print_output<T: serde::Serialize>(data: &T, format: &str) -> Result<(), anyhow::Error> {
     match format {
         "yaml" => {
             let yaml_output = serde_yaml::to_string(data)?;
             println!("{}", yaml_output);
         }
-        "json" | _ => {
+        "ics" => {
+            anyhow::bail!("--format ics is only supported by `price forecast`");
+        }
+        _ => {
             let json_output = serde_json::to_string(data)?;
             println!("{}", json_output);
         }
     }
     Ok(())
 }
+
+// Report rows can also be printed as CSV, with a header row.
+fn print_rows<T: serde::Serialize>(rows: &[T], format: &str) -> Result<(), anyhow::Error> {
+    match format {
+        "csv" => {
+            let mut writer = csv::Writer::from_writer(std::io::stdout());
+            for row in rows {
+                writer.serialize(row)?;
+            }
+            writer.flush()?;
+            Ok(())
+        }
+        _ => print_output(&rows, format),
+    }
+}
+
+// Time series data can also be printed as InfluxDB line protocol, tagged with `id`.
+fn print_series<T: serde::Serialize + LineProtocol>(
+    data: &[T],
+    id: &str,
+    format: &str,
+) -> Result<(), anyhow::Error> {
+    match format {
+        "influx" => {
+            println!("{}", to_line_protocol(data, id));
+            Ok(())
+        }
+        _ => print_output(&data, format),
+    }
+}
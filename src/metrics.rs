@@ -0,0 +1,288 @@
+use anyhow::Result;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use iso8601_timestamp::Timestamp;
+use std::convert::Infallible;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::{error, info};
+
+use crate::get_prices;
+use crate::rest_client::PriceData;
+use crate::secret::SecretString;
+use crate::spike::FORECAST_INTERVAL;
+use crate::watch::{duration_until_next_interval, unix_now, CURRENT_INTERVAL};
+
+/// Delay before retrying a failed refresh, doubled on every following failure.
+const RETRY_BACKOFF: Duration = Duration::from_secs(15);
+
+/// Struct type holding the latest data fetched from Amber, rendered on every scrape.
+#[derive(Debug, Default, Clone)]
+pub struct MetricsSnapshot {
+    pub prices: Vec<PriceData>,
+    pub last_success: Option<u64>,
+    pub api_errors: u64,
+}
+
+/// Map Amber's spike status onto a number, so it can be graphed and alerted on.
+pub fn spike_status_value(spike_status: &str) -> u8 {
+    match spike_status {
+        "potential" => 1,
+        "spike" => 2,
+        _ => 0,
+    }
+}
+
+/// Write the HELP and TYPE lines for a metric.
+fn describe(output: &mut String, name: &str, metric_type: &str, help: &str) {
+    let _ = writeln!(output, "# HELP {} {}", name, help);
+    let _ = writeln!(output, "# TYPE {} {}", name, metric_type);
+}
+
+/// Render the snapshot in the Prometheus text exposition format, at `now` (unix seconds).
+/// Intervals that have ended, left over when refreshing fails, are not exported.
+pub fn render_metrics(site_id: &str, snapshot: &MetricsSnapshot, now: u64) -> String {
+    let mut output = String::new();
+    let now = Timestamp::UNIX_EPOCH + time::Duration::seconds(now as i64);
+    let prices: Vec<&PriceData> = snapshot
+        .prices
+        .iter()
+        .filter(|interval| interval.end_time > now)
+        .collect();
+    let current: Vec<&PriceData> = prices
+        .iter()
+        .copied()
+        .filter(|interval| interval.interval_type == CURRENT_INTERVAL)
+        .collect();
+
+    describe(
+        &mut output,
+        "amber_price_per_kwh",
+        "gauge",
+        "Price in c/kWh for the current and next interval, per channel.",
+    );
+    for interval in &current {
+        let _ = writeln!(
+            output,
+            "amber_price_per_kwh{{site_id=\"{}\",channel=\"{}\",interval=\"current\"}} {}",
+            site_id, interval.channel_type, interval.per_kwh
+        );
+        let next = prices
+            .iter()
+            .filter(|forecast| {
+                forecast.interval_type == FORECAST_INTERVAL
+                    && forecast.channel_type == interval.channel_type
+            })
+            .min_by_key(|forecast| forecast.start_time);
+        if let Some(next) = next {
+            let _ = writeln!(
+                output,
+                "amber_price_per_kwh{{site_id=\"{}\",channel=\"{}\",interval=\"next\"}} {}",
+                site_id, interval.channel_type, next.per_kwh
+            );
+        }
+    }
+
+    describe(
+        &mut output,
+        "amber_spot_price_per_kwh",
+        "gauge",
+        "Wholesale spot price in c/kWh for the current interval.",
+    );
+    describe(
+        &mut output,
+        "amber_renewables_percent",
+        "gauge",
+        "Percentage of renewables in the grid for the current interval.",
+    );
+    if let Some(general) = current.iter().find(|i| i.channel_type == "general") {
+        let _ = writeln!(
+            output,
+            "amber_spot_price_per_kwh{{site_id=\"{}\"}} {}",
+            site_id, general.spot_per_kwh
+        );
+        let _ = writeln!(
+            output,
+            "amber_renewables_percent{{site_id=\"{}\"}} {}",
+            site_id, general.renewables
+        );
+    }
+
+    describe(
+        &mut output,
+        "amber_spike_status",
+        "gauge",
+        "Spike status of the current interval, 0 none, 1 potential, 2 spike.",
+    );
+    for interval in &current {
+        let _ = writeln!(
+            output,
+            "amber_spike_status{{site_id=\"{}\",channel=\"{}\"}} {}",
+            site_id,
+            interval.channel_type,
+            spike_status_value(&interval.spike_status)
+        );
+    }
+
+    describe(
+        &mut output,
+        "amber_forecast_price_min_per_kwh",
+        "gauge",
+        "Lowest forecast price in c/kWh, per channel.",
+    );
+    describe(
+        &mut output,
+        "amber_forecast_price_max_per_kwh",
+        "gauge",
+        "Highest forecast price in c/kWh, per channel.",
+    );
+    for interval in &current {
+        let forecast_prices: Vec<f32> = prices
+            .iter()
+            .filter(|forecast| {
+                forecast.interval_type == FORECAST_INTERVAL
+                    && forecast.channel_type == interval.channel_type
+            })
+            .map(|forecast| forecast.per_kwh)
+            .collect();
+        if forecast_prices.is_empty() {
+            continue;
+        }
+        let min = forecast_prices.iter().copied().fold(f32::MAX, f32::min);
+        let max = forecast_prices.iter().copied().fold(f32::MIN, f32::max);
+        let _ = writeln!(
+            output,
+            "amber_forecast_price_min_per_kwh{{site_id=\"{}\",channel=\"{}\"}} {}",
+            site_id, interval.channel_type, min
+        );
+        let _ = writeln!(
+            output,
+            "amber_forecast_price_max_per_kwh{{site_id=\"{}\",channel=\"{}\"}} {}",
+            site_id, interval.channel_type, max
+        );
+    }
+
+    describe(
+        &mut output,
+        "amber_last_success_timestamp_seconds",
+        "gauge",
+        "Unix time of the last successful update from the Amber API.",
+    );
+    if let Some(last_success) = snapshot.last_success {
+        let _ = writeln!(
+            output,
+            "amber_last_success_timestamp_seconds{{site_id=\"{}\"}} {}",
+            site_id, last_success
+        );
+    }
+
+    describe(
+        &mut output,
+        "amber_api_errors_total",
+        "counter",
+        "Number of failed updates from the Amber API.",
+    );
+    let _ = writeln!(
+        output,
+        "amber_api_errors_total{{site_id=\"{}\"}} {}",
+        site_id, snapshot.api_errors
+    );
+
+    output
+}
+
+/// Fetch the current and forecast prices and store them in the snapshot, returning whether it
+/// succeeded. Failures only bump the error counter, so the last good data keeps being served.
+#[tracing::instrument(level = "debug", skip(auth_token, snapshot))]
+pub async fn refresh_metrics(
+    base_url: String,
+    auth_token: SecretString,
+    site_id: String,
+    forecast_intervals: u32,
+    snapshot: &RwLock<MetricsSnapshot>,
+) -> bool {
+    let window = format!("current?next={}", forecast_intervals);
+    match get_prices(base_url, auth_token, site_id, window).await {
+        Ok(prices) => {
+            let mut snapshot = snapshot.write().await;
+            snapshot.prices = prices;
+            snapshot.last_success = Some(unix_now());
+            true
+        }
+        Err(error) => {
+            error!("Failed to refresh metrics from Amber API: {}", error);
+            snapshot.write().await.api_errors += 1;
+            false
+        }
+    }
+}
+
+/// Run the Prometheus exporter, serving "/metrics" on `listen`.
+/// Amber is polled once per interval in the background rather than on every scrape.
+#[tracing::instrument(level = "debug", skip(auth_token))]
+pub async fn serve_metrics(
+    base_url: String,
+    auth_token: SecretString,
+    site_id: String,
+    listen: SocketAddr,
+    forecast_intervals: u32,
+) -> Result<()> {
+    let snapshot = Arc::new(RwLock::new(MetricsSnapshot::default()));
+
+    let refresh_snapshot = snapshot.clone();
+    let refresh_site_id = site_id.clone();
+    tokio::spawn(async move {
+        let mut failures = 0;
+        loop {
+            let refreshed = refresh_metrics(
+                base_url.clone(),
+                auth_token.clone(),
+                refresh_site_id.clone(),
+                forecast_intervals,
+                &refresh_snapshot,
+            )
+            .await;
+            // Retry failures with a backoff, but never sleep past the next interval.
+            let mut sleep = duration_until_next_interval(unix_now());
+            if refreshed {
+                failures = 0;
+            } else {
+                sleep = sleep.min(RETRY_BACKOFF * 2u32.pow(failures.min(6)));
+                failures += 1;
+            }
+            tokio::time::sleep(sleep).await;
+        }
+    });
+
+    let make_service = make_service_fn(move |_connection| {
+        let snapshot = snapshot.clone();
+        let site_id = site_id.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                let snapshot = snapshot.clone();
+                let site_id = site_id.clone();
+                async move {
+                    match (request.method(), request.uri().path()) {
+                        (&Method::GET, "/metrics") => {
+                            let body =
+                                render_metrics(&site_id, &*snapshot.read().await, unix_now());
+                            Response::builder()
+                                .header("Content-Type", "text/plain; version=0.0.4")
+                                .body(Body::from(body))
+                        }
+                        _ => Response::builder()
+                            .status(StatusCode::NOT_FOUND)
+                            .body(Body::from("Not found")),
+                    }
+                }
+            }))
+        }
+    });
+
+    info!("Serving Prometheus metrics on http://{}/metrics", listen);
+    Server::try_bind(&listen)?.serve(make_service).await?;
+    Ok(())
+}
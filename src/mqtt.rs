@@ -0,0 +1,255 @@
+use anyhow::{Context, Result};
+use rumqttc::{AsyncClient, Event, EventLoop, LastWill, MqttOptions, Packet, QoS, Transport};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+use crate::app_config::MqttConfig;
+use crate::home_assistant::discovery_messages;
+use crate::rest_client::{PriceData, SiteDetails};
+use crate::spike::FORECAST_INTERVAL;
+use crate::watch::{WatchUpdate, CURRENT_INTERVAL};
+
+/// Payload published to the status topic while connected.
+pub const ONLINE: &str = "online";
+
+/// Payload the broker publishes to the status topic, as our last will, if we drop off.
+pub const OFFLINE: &str = "offline";
+
+/// Struct type for a single message to publish.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MqttMessage {
+    pub topic: String,
+    pub payload: String,
+}
+
+/// Struct type for the forecast of a channel, published as a JSON object so
+/// it can be used as Home Assistant sensor attributes.
+#[derive(Serialize, Debug)]
+pub struct ForecastAttributes<'a> {
+    pub forecasts: Vec<&'a PriceData>,
+}
+
+/// Topic the online/offline status is published to.
+pub fn status_topic(topic_prefix: &str) -> String {
+    format!("{}/status", topic_prefix)
+}
+
+/// Topic the current interval of a channel is published to.
+pub fn price_topic(topic_prefix: &str, site_id: &str, channel_type: &str) -> String {
+    format!("{}/{}/price/{}", topic_prefix, site_id, channel_type)
+}
+
+/// Topic the forecast intervals of a channel are published to.
+pub fn forecast_topic(topic_prefix: &str, site_id: &str, channel_type: &str) -> String {
+    format!("{}/{}/forecast/{}", topic_prefix, site_id, channel_type)
+}
+
+/// Build the messages to publish for a watch mode poll.
+/// The current interval of each channel is published as JSON to "<prefix>/<site>/price/<channel>",
+/// its forecast intervals to "<prefix>/<site>/forecast/<channel>",
+/// the general channel's renewables percentage to "<prefix>/<site>/renewables"
+/// and its spike status to "<prefix>/<site>/spike".
+pub fn update_messages(
+    topic_prefix: &str,
+    site_id: &str,
+    update: &WatchUpdate,
+) -> Vec<MqttMessage> {
+    let current_intervals: Vec<_> = update
+        .prices
+        .iter()
+        .filter(|interval| interval.interval_type == CURRENT_INTERVAL)
+        .collect();
+
+    let mut messages = Vec::new();
+    for interval in &current_intervals {
+        // PriceData only contains plain fields, so serialising can not fail.
+        let payload = serde_json::to_string(interval).unwrap_or_default();
+        messages.push(MqttMessage {
+            topic: price_topic(topic_prefix, site_id, &interval.channel_type),
+            payload,
+        });
+
+        let forecast = ForecastAttributes {
+            forecasts: update
+                .prices
+                .iter()
+                .filter(|forecast| {
+                    forecast.interval_type == FORECAST_INTERVAL
+                        && forecast.channel_type == interval.channel_type
+                })
+                .collect(),
+        };
+        if !forecast.forecasts.is_empty() {
+            messages.push(MqttMessage {
+                topic: forecast_topic(topic_prefix, site_id, &interval.channel_type),
+                payload: serde_json::to_string(&forecast).unwrap_or_default(),
+            });
+        }
+    }
+
+    let general = current_intervals
+        .iter()
+        .find(|interval| interval.channel_type == "general")
+        .or(current_intervals.first());
+    if let Some(general) = general {
+        messages.push(MqttMessage {
+            topic: format!("{}/{}/renewables", topic_prefix, site_id),
+            payload: general.renewables.to_string(),
+        });
+        messages.push(MqttMessage {
+            topic: format!("{}/{}/spike", topic_prefix, site_id),
+            payload: general.spike_status.clone(),
+        });
+    }
+
+    messages
+}
+
+/// Retained messages published again every time the broker (re)connects.
+type RetainedMessages = Arc<Mutex<Vec<MqttMessage>>>;
+
+/// Struct type for our connection to the MQTT broker.
+pub struct MqttPublisher {
+    pub config: MqttConfig,
+    pub client: AsyncClient,
+    qos: QoS,
+    retained: RetainedMessages,
+}
+
+impl MqttPublisher {
+    /// Connect to the broker and drive the connection from a background task.
+    /// The broker is told to publish "offline" to the status topic if the connection drops,
+    /// and "online" is published again each time the connection comes back.
+    pub async fn connect(config: MqttConfig) -> Result<Self> {
+        let qos = rumqttc::qos(config.qos)
+            .map_err(|_| anyhow::anyhow!("MQTT qos must be 0, 1 or 2, not {}", config.qos))?;
+
+        let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+        options.set_keep_alive(Duration::from_secs(30));
+        options.set_last_will(LastWill::new(
+            status_topic(&config.topic_prefix),
+            OFFLINE,
+            qos,
+            true,
+        ));
+        // A username without a password is still sent, with an empty password, not dropped.
+        match (&config.username, &config.password) {
+            (Some(username), password) => {
+                let password = password
+                    .as_ref()
+                    .map_or("", |password| password.expose_secret());
+                options.set_credentials(username, password);
+            }
+            (None, Some(_)) => anyhow::bail!("MQTT password is set without a username"),
+            (None, None) => (),
+        }
+        if config.tls {
+            let transport = match &config.ca_file {
+                Some(ca_file) => {
+                    let ca = std::fs::read(ca_file)
+                        .with_context(|| format!("Failed to read MQTT CA file {}", ca_file))?;
+                    Transport::tls(ca, None, None)
+                }
+                None => Transport::tls_with_default_config(),
+            };
+            options.set_transport(transport);
+        }
+
+        let retained = Arc::new(Mutex::new(vec![MqttMessage {
+            topic: status_topic(&config.topic_prefix),
+            payload: ONLINE.to_string(),
+        }]));
+        let (client, event_loop) = AsyncClient::new(options, 10);
+        tokio::spawn(drive_event_loop(
+            event_loop,
+            client.clone(),
+            qos,
+            retained.clone(),
+        ));
+
+        Ok(Self {
+            config,
+            client,
+            qos,
+            retained,
+        })
+    }
+
+    /// Publish the prices, renewables and spike status of a watch mode poll.
+    pub async fn publish_update(&self, site_id: &str, update: &WatchUpdate) -> Result<()> {
+        for message in update_messages(&self.config.topic_prefix, site_id, update) {
+            self.publish(message).await?;
+        }
+        Ok(())
+    }
+
+    /// Publish Home Assistant discovery config for the site, always retained so
+    /// Home Assistant picks the entities up again after it restarts.
+    pub async fn publish_discovery(&self, site: &SiteDetails) -> Result<()> {
+        let messages = discovery_messages(
+            &self.config.discovery_prefix,
+            &self.config.topic_prefix,
+            site,
+        );
+        if let Ok(mut retained) = self.retained.lock() {
+            retained.extend(messages.iter().cloned());
+        }
+        for message in messages {
+            debug!("Publishing Home Assistant discovery to {}", message.topic);
+            self.client
+                .publish(message.topic, self.qos, true, message.payload)
+                .await?;
+        }
+        Ok(())
+    }
+
+    pub async fn publish(&self, message: MqttMessage) -> Result<()> {
+        debug!("Publishing to MQTT topic {}", message.topic);
+        self.client
+            .publish(message.topic, self.qos, self.config.retain, message.payload)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Poll the rumqttc event loop, which also handles reconnecting to the broker.
+/// The broker publishes our "offline" last will when the connection drops, so the retained
+/// status and discovery messages are published again on every connection acknowledgement.
+async fn drive_event_loop(
+    mut event_loop: EventLoop,
+    client: AsyncClient,
+    qos: QoS,
+    retained: RetainedMessages,
+) {
+    loop {
+        match event_loop.poll().await {
+            Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                debug!("Connected to the MQTT broker, publishing the online status");
+                let messages = retained
+                    .lock()
+                    .map(|retained| retained.clone())
+                    .unwrap_or_default();
+                // Publishing waits on this event loop, so it has to happen on another task.
+                tokio::spawn(publish_retained(client.clone(), qos, messages));
+            }
+            Ok(_) => (),
+            Err(error) => {
+                warn!("MQTT connection error, reconnecting: {}", error);
+                tokio::time::sleep(Duration::from_secs(5)).await;
+            }
+        }
+    }
+}
+
+async fn publish_retained(client: AsyncClient, qos: QoS, messages: Vec<MqttMessage>) {
+    for message in messages {
+        if let Err(error) = client
+            .publish(message.topic, qos, true, message.payload)
+            .await
+        {
+            warn!("Failed to publish retained MQTT message: {}", error);
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use anyhow::Result;
+use hmac::{Hmac, Mac};
+use iso8601_timestamp::Timestamp;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Client;
+use serde::Serialize;
+use sha2::Sha256;
+use std::time::Duration;
+use tracing::{error, warn};
+
+use crate::app_config::{AppConfig, WebhookConfig};
+use crate::influx::InfluxWriter;
+use crate::mqtt::MqttPublisher;
+use crate::rest_client::{Error, PriceData};
+use crate::watch::{Alert, WatchUpdate, CURRENT_INTERVAL};
+
+/// Header carrying the HMAC-SHA256 signature of the payload body, as "sha256=<hex>".
+pub const SIGNATURE_HEADER: &str = "X-Amber-Signature";
+
+/// Delay before the first retry of a failed webhook, doubled on every following attempt.
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Enum type to describe why a notification was sent.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum NotificationEvent {
+    Alert,
+    Interval,
+}
+
+/// Struct type for the JSON body POSTed to webhooks.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationPayload {
+    pub event: NotificationEvent,
+    pub site_id: String,
+    pub sent_at: Timestamp,
+    pub alert: Option<Alert>,
+    pub intervals: Vec<PriceData>,
+}
+
+/// Struct type for a single configured webhook endpoint.
+pub struct WebhookNotifier {
+    pub config: WebhookConfig,
+    client: Client,
+}
+
+impl WebhookNotifier {
+    pub fn new(config: WebhookConfig) -> Result<Self> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(config.timeout_seconds))
+            .build()?;
+        Ok(Self { config, client })
+    }
+
+    /// Sign a payload body with the shared secret, returning the value for `SIGNATURE_HEADER`.
+    pub fn sign(secret: &str, body: &[u8]) -> String {
+        // HMAC accepts keys of any length, so this can not fail.
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+            .expect("HMAC can take a key of any size");
+        mac.update(body);
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+    }
+
+    /// POST the payload to the webhook, retrying failed attempts with a backoff.
+    #[tracing::instrument(level = "debug", skip(self, payload), fields(url = %self.config.url))]
+    pub async fn send(&self, payload: &NotificationPayload) -> Result<()> {
+        let body = serde_json::to_string(payload)?;
+        let mut attempt = 0;
+
+        loop {
+            match self.post(&body).await {
+                Ok(()) => return Ok(()),
+                Err(error) if attempt < self.config.retries => {
+                    let backoff = RETRY_BACKOFF * 2u32.pow(attempt);
+                    warn!(
+                        "Webhook attempt {} failed, retrying in {:?}: {}",
+                        attempt + 1,
+                        backoff,
+                        error
+                    );
+                    tokio::time::sleep(backoff).await;
+                    attempt += 1;
+                }
+                Err(error) => return Err(error.into()),
+            }
+        }
+    }
+
+    async fn post(&self, body: &str) -> Result<(), Error> {
+        let mut request = self
+            .client
+            .post(&self.config.url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body.to_string());
+        for (name, value) in &self.config.headers {
+            request = request.header(name, value.expose_secret());
+        }
+        if let Some(secret) = &self.config.secret {
+            request = request.header(
+                SIGNATURE_HEADER,
+                Self::sign(secret.expose_secret(), body.as_bytes()),
+            );
+        }
+
+        let response = request.send().await?;
+        if response.status().is_success() {
+            return Ok(());
+        }
+        Err(Error::HttpNon200Status {
+            status_code: (response.status().to_string()),
+            body: (response.text().await)?,
+        })
+    }
+}
+
+/// Struct type holding every notifier backend watch mode sends updates to.
+#[derive(Default)]
+pub struct Notifiers {
+    pub webhooks: Vec<WebhookNotifier>,
+    pub mqtt: Option<MqttPublisher>,
+    pub influx: Option<InfluxWriter>,
+}
+
+impl Notifiers {
+    pub async fn from_config(config: &AppConfig) -> Result<Self> {
+        let webhooks = config
+            .webhook
+            .iter()
+            .cloned()
+            .map(WebhookNotifier::new)
+            .collect::<Result<Vec<_>>>()?;
+        let mqtt = match &config.mqtt {
+            Some(mqtt_config) => Some(MqttPublisher::connect(mqtt_config.clone()).await?),
+            None => None,
+        };
+        let influx = config.influxdb.clone().map(InfluxWriter::new).transpose()?;
+        Ok(Self {
+            webhooks,
+            mqtt,
+            influx,
+        })
+    }
+
+    /// Send the alerts, and for webhooks that asked for them the interval data, of a watch poll.
+    /// Failures are logged so one broken endpoint does not stop watch mode.
+    pub async fn dispatch(&self, site_id: &str, update: &WatchUpdate) {
+        if let Some(mqtt) = &self.mqtt {
+            if let Err(error) = mqtt.publish_update(site_id, update).await {
+                error!("Failed to publish to MQTT: {}", error);
+            }
+        }
+
+        let intervals: Vec<PriceData> = update
+            .prices
+            .iter()
+            .filter(|interval| interval.interval_type == CURRENT_INTERVAL)
+            .cloned()
+            .collect();
+
+        if let Some(influx) = &self.influx {
+            if let Err(error) = influx.write(&intervals, site_id).await {
+                error!("Failed to write to InfluxDB: {}", error);
+            }
+        }
+
+        let mut payloads: Vec<NotificationPayload> = update
+            .alerts
+            .iter()
+            .map(|alert| NotificationPayload {
+                event: NotificationEvent::Alert,
+                site_id: site_id.to_string(),
+                sent_at: Timestamp::now_utc(),
+                alert: Some(alert.clone()),
+                intervals: intervals.clone(),
+            })
+            .collect();
+        payloads.push(NotificationPayload {
+            event: NotificationEvent::Interval,
+            site_id: site_id.to_string(),
+            sent_at: Timestamp::now_utc(),
+            alert: None,
+            intervals,
+        });
+
+        for webhook in &self.webhooks {
+            for payload in &payloads {
+                if payload.event == NotificationEvent::Interval && !webhook.config.on_interval {
+                    continue;
+                }
+                if let Err(error) = webhook.send(payload).await {
+                    error!("Failed to notify {}: {}", webhook.config.url, error);
+                }
+            }
+        }
+    }
+}
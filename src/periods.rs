@@ -0,0 +1,137 @@
+use iso8601_timestamp::Timestamp;
+use serde::Serialize;
+
+use crate::calendar::{format_ics_time, CalendarEvent};
+use crate::planner::plannable_intervals;
+use crate::rest_client::PriceData;
+use crate::schedule::nem_clock_time;
+
+/// Descriptors Amber gives intervals with a low price.
+const CHEAP_DESCRIPTORS: [&str; 3] = ["extremelyLow", "veryLow", "low"];
+
+/// Enum type for the kinds of price period worth putting in a calendar.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum PeriodKind {
+    Cheap,
+    Spike,
+}
+
+impl PeriodKind {
+    fn name(&self) -> &'static str {
+        match self {
+            PeriodKind::Cheap => "cheap",
+            PeriodKind::Spike => "spike",
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            PeriodKind::Cheap => "Cheap power",
+            PeriodKind::Spike => "Price spike",
+        }
+    }
+}
+
+/// Struct type for the price limits used instead of Amber's descriptors, in c/kWh.
+#[derive(Debug, Clone, Default)]
+pub struct PeriodThresholds {
+    pub cheap_below: Option<f32>,
+    pub spike_above: Option<f32>,
+}
+
+impl PeriodThresholds {
+    /// Which period an interval belongs to, if any. A threshold replaces the descriptor check.
+    pub fn classify(&self, interval: &PriceData) -> Option<PeriodKind> {
+        let spike = match self.spike_above {
+            Some(limit) => interval.per_kwh > limit,
+            None => interval.descriptor == "spike" || interval.spike_status == "spike",
+        };
+        let cheap = match self.cheap_below {
+            Some(limit) => interval.per_kwh < limit,
+            None => CHEAP_DESCRIPTORS.contains(&interval.descriptor.as_str()),
+        };
+        if spike {
+            Some(PeriodKind::Spike)
+        } else if cheap {
+            Some(PeriodKind::Cheap)
+        } else {
+            None
+        }
+    }
+}
+
+/// Struct type for consecutive intervals of the same kind.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PricePeriod {
+    pub kind: PeriodKind,
+    pub channel_type: String,
+    pub start_time: Timestamp,
+    pub end_time: Timestamp,
+    pub intervals: usize,
+    pub average_per_kwh: f32,
+}
+
+/// Group consecutive current and forecast intervals of a channel into cheap and spike periods.
+pub fn find_price_periods(
+    prices: &[PriceData],
+    channel_type: &str,
+    thresholds: &PeriodThresholds,
+) -> Vec<PricePeriod> {
+    let mut periods: Vec<PricePeriod> = Vec::new();
+    let mut previous: Option<PeriodKind> = None;
+    for interval in plannable_intervals(prices, channel_type, u32::MAX) {
+        let kind = thresholds.classify(interval);
+        match (kind, periods.last_mut()) {
+            (Some(kind), Some(period)) if previous == Some(kind) => {
+                period.average_per_kwh = (period.average_per_kwh * period.intervals as f32
+                    + interval.per_kwh)
+                    / (period.intervals + 1) as f32;
+                period.end_time = interval.end_time;
+                period.intervals += 1;
+            }
+            (Some(kind), _) => periods.push(PricePeriod {
+                kind,
+                channel_type: channel_type.to_string(),
+                start_time: interval.start_time,
+                end_time: interval.end_time,
+                intervals: 1,
+                average_per_kwh: interval.per_kwh,
+            }),
+            (None, _) => (),
+        }
+        previous = kind;
+    }
+    periods
+}
+
+impl PricePeriod {
+    /// Calendar event for the period, e.g. "Cheap power 11:00–14:30, avg 4.2c".
+    /// The UID is built from the kind, channel and start, so a period whose end moves keeps
+    /// its UID, but one whose start moves (e.g. its first interval has passed, or the forecast
+    /// was revised) gets a new UID.
+    pub fn calendar_event(&self) -> CalendarEvent {
+        CalendarEvent {
+            uid: format!(
+                "price-{}-{}-{}@amber-cli",
+                self.kind.name(),
+                self.channel_type,
+                format_ics_time(&self.start_time)
+            ),
+            start_time: self.start_time,
+            end_time: self.end_time,
+            summary: format!(
+                "{} {}–{}, avg {:.1}c",
+                self.kind.title(),
+                nem_clock_time(&self.start_time),
+                nem_clock_time(&self.end_time),
+                self.average_per_kwh
+            ),
+            description: Some(format!(
+                "{} channel, {} intervals averaging {:.2}c/kWh",
+                self.channel_type, self.intervals, self.average_per_kwh
+            )),
+        }
+    }
+}
@@ -0,0 +1,262 @@
+use anyhow::{anyhow, bail, Result};
+use clap::ValueEnum;
+use iso8601_timestamp::Timestamp;
+use serde::Serialize;
+
+use crate::get_prices;
+use crate::rest_client::PriceData;
+use crate::secret::SecretString;
+use crate::spike::FORECAST_INTERVAL;
+use crate::watch::CURRENT_INTERVAL;
+
+/// Enum type for what the planner optimises for.
+#[derive(ValueEnum, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum Objective {
+    /// Lowest average price.
+    Price,
+    /// Highest average renewables percentage in the grid.
+    Renewables,
+    /// Lowest price, less `carbon_weight` c/kWh for each percentage point of renewables.
+    Weighted,
+}
+
+impl Objective {
+    /// Score an interval, lower is better.
+    pub fn score(&self, interval: &PriceData, carbon_weight: f32) -> f32 {
+        match self {
+            Objective::Price => interval.per_kwh,
+            Objective::Renewables => -interval.renewables,
+            Objective::Weighted => interval.per_kwh - carbon_weight * interval.renewables,
+        }
+    }
+}
+
+/// Struct type holding the options for finding the best time to run a load.
+#[derive(Debug, Clone)]
+pub struct PlanOptions {
+    pub duration_minutes: u32,
+    pub within_minutes: u32,
+    pub channel_type: String,
+    /// Run the load in one go, rather than split over the best intervals.
+    pub contiguous: bool,
+    pub objective: Objective,
+    /// How many c/kWh each percentage point of renewables is worth to the weighted objective.
+    pub carbon_weight: f32,
+    /// Power the load draws in kW, used to estimate the cost in cents.
+    pub power_kw: Option<f32>,
+}
+
+/// Struct type for a continuous run of intervals the load should run for.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanRun {
+    pub start_time: Timestamp,
+    pub end_time: Timestamp,
+    pub intervals: usize,
+}
+
+/// Struct type for the recommended time to run a load.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadPlan {
+    pub channel_type: String,
+    pub objective: Objective,
+    pub runs: Vec<PlanRun>,
+    pub average_per_kwh: f32,
+    pub average_renewables: f32,
+    /// Average price if the load was started now instead.
+    pub now_average_per_kwh: f32,
+    /// Average renewables percentage if the load was started now instead.
+    pub now_average_renewables: f32,
+    pub savings_per_kwh: f32,
+    pub savings_percent: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimated_cost: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimated_savings: Option<f32>,
+}
+
+/// Parse a duration such as "2h", "90m" or "1h30m" into minutes.
+pub fn parse_duration_minutes(value: &str) -> Result<u32> {
+    let too_long = || anyhow!("Duration {} is too long", value);
+    let mut minutes: u32 = 0;
+    let mut number = String::new();
+    for c in value.trim().chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'h' | 'm' => {
+                let amount: u32 = number.parse().map_err(|_| {
+                    anyhow!("Invalid duration {}, use e.g. 2h, 90m or 1h30m", value)
+                })?;
+                let amount = match c {
+                    'h' => amount.checked_mul(60).ok_or_else(too_long)?,
+                    _ => amount,
+                };
+                minutes = minutes.checked_add(amount).ok_or_else(too_long)?;
+                number.clear();
+            }
+            _ => bail!("Invalid duration {}, use e.g. 2h, 90m or 1h30m", value),
+        }
+    }
+    // A bare number is taken as minutes.
+    if !number.is_empty() {
+        let amount: u32 = number.parse().map_err(|_| too_long())?;
+        minutes = minutes.checked_add(amount).ok_or_else(too_long)?;
+    }
+    if minutes == 0 {
+        bail!("Duration {} must be longer than zero", value);
+    }
+    Ok(minutes)
+}
+
+/// Current and forecast intervals of a channel that fall inside the planning horizon, in order.
+pub fn plannable_intervals<'a>(
+    prices: &'a [PriceData],
+    channel_type: &str,
+    within_minutes: u32,
+) -> Vec<&'a PriceData> {
+    let mut intervals: Vec<&PriceData> = prices
+        .iter()
+        .filter(|interval| {
+            (interval.interval_type == CURRENT_INTERVAL
+                || interval.interval_type == FORECAST_INTERVAL)
+                && interval.channel_type == channel_type
+        })
+        .collect();
+    intervals.sort_by_key(|interval| interval.start_time);
+
+    let mut horizon = 0;
+    intervals
+        .into_iter()
+        .take_while(|interval| {
+            let inside = horizon < within_minutes;
+            horizon += u32::from(interval.duration);
+            inside
+        })
+        .collect()
+}
+
+/// Group chosen intervals, in time order, into continuous runs.
+pub fn group_runs(chosen: &[&PriceData]) -> Vec<PlanRun> {
+    let mut runs: Vec<PlanRun> = Vec::new();
+    for interval in chosen {
+        match runs.last_mut() {
+            // Amber intervals start one second after the previous one ends.
+            Some(run)
+                if interval
+                    .start_time
+                    .duration_since(run.end_time)
+                    .whole_seconds()
+                    <= 1 =>
+            {
+                run.end_time = interval.end_time;
+                run.intervals += 1;
+            }
+            _ => runs.push(PlanRun {
+                start_time: interval.start_time,
+                end_time: interval.end_time,
+                intervals: 1,
+            }),
+        }
+    }
+    runs
+}
+
+fn average(values: impl Iterator<Item = f32>) -> f32 {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), value| (sum + value, count + 1));
+    match count {
+        0 => 0.0,
+        _ => sum / count as f32,
+    }
+}
+
+/// Estimated cost in cents of running a `power_kw` load over the intervals.
+pub fn estimated_cost(intervals: &[&PriceData], power_kw: f32) -> f32 {
+    intervals
+        .iter()
+        .map(|interval| interval.per_kwh * power_kw * f32::from(interval.duration) / 60.0)
+        .sum()
+}
+
+/// Find the best interval(s) to run a load for `duration_minutes` in the forecast.
+pub fn find_cheapest_window(prices: &[PriceData], options: &PlanOptions) -> Result<LoadPlan> {
+    let intervals = plannable_intervals(prices, &options.channel_type, options.within_minutes);
+    let interval_minutes = match intervals.first() {
+        Some(interval) => u32::from(interval.duration),
+        None => bail!("No price forecast for the {} channel", options.channel_type),
+    };
+    let needed = options.duration_minutes.div_ceil(interval_minutes) as usize;
+    if needed > intervals.len() {
+        bail!(
+            "Only {} intervals of forecast are available within the planning window, {} are needed",
+            intervals.len(),
+            needed
+        );
+    }
+
+    let score = |interval: &PriceData| options.objective.score(interval, options.carbon_weight);
+    let chosen: Vec<&PriceData> = if options.contiguous {
+        let best_start = (0..=intervals.len() - needed)
+            .min_by(|a, b| {
+                let score_a: f32 = intervals[*a..*a + needed].iter().map(|i| score(i)).sum();
+                let score_b: f32 = intervals[*b..*b + needed].iter().map(|i| score(i)).sum();
+                score_a.total_cmp(&score_b)
+            })
+            .unwrap_or(0);
+        intervals[best_start..best_start + needed].to_vec()
+    } else {
+        let mut ranked = intervals.clone();
+        // A stable sort keeps earlier intervals first when scores tie.
+        ranked.sort_by(|a, b| score(a).total_cmp(&score(b)));
+        let mut chosen: Vec<&PriceData> = ranked.into_iter().take(needed).collect();
+        chosen.sort_by_key(|interval| interval.start_time);
+        chosen
+    };
+    let now: Vec<&PriceData> = intervals[..needed].to_vec();
+
+    let average_per_kwh = average(chosen.iter().map(|i| i.per_kwh));
+    let now_average_per_kwh = average(now.iter().map(|i| i.per_kwh));
+    let savings_per_kwh = now_average_per_kwh - average_per_kwh;
+    let savings_percent = match now_average_per_kwh {
+        price if price.abs() > f32::EPSILON => savings_per_kwh / price.abs() * 100.0,
+        _ => 0.0,
+    };
+
+    Ok(LoadPlan {
+        channel_type: options.channel_type.clone(),
+        objective: options.objective,
+        runs: group_runs(&chosen),
+        average_per_kwh,
+        average_renewables: average(chosen.iter().map(|i| i.renewables)),
+        now_average_per_kwh,
+        now_average_renewables: average(now.iter().map(|i| i.renewables)),
+        savings_per_kwh,
+        savings_percent,
+        estimated_cost: options.power_kw.map(|kw| estimated_cost(&chosen, kw)),
+        estimated_savings: options
+            .power_kw
+            .map(|kw| estimated_cost(&now, kw) - estimated_cost(&chosen, kw)),
+    })
+}
+
+/// Number of forecast intervals to request to cover `within_minutes`.
+pub fn forecast_intervals_for(within_minutes: u32) -> u32 {
+    within_minutes.div_ceil(30)
+}
+
+/// Function to fetch the forecast and find the cheapest (or greenest) time to run a load.
+#[tracing::instrument(level = "debug", skip(auth_token))]
+pub async fn plan_cheapest(
+    base_url: String,
+    auth_token: SecretString,
+    site_id: String,
+    options: PlanOptions,
+) -> Result<LoadPlan> {
+    let window = format!(
+        "current?next={}",
+        forecast_intervals_for(options.within_minutes)
+    );
+    let forecast_price_data = get_prices(base_url, auth_token, site_id, window).await?;
+    find_cheapest_window(&forecast_price_data, &options)
+}
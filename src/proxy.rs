@@ -0,0 +1,268 @@
+use anyhow::Result;
+use chrono::{NaiveDate, TimeZone, Utc};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use reqwest::Url;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::{debug, error, info};
+
+use crate::schedule::NEM_UTC_OFFSET_HOURS;
+use crate::secret::SecretString;
+use crate::watch::{next_interval_boundary, unix_now};
+use crate::{get_prices, get_renewables, get_site_data, get_usage_by_date};
+
+/// Header telling clients whether the response came from the cache.
+pub const CACHE_HEADER: &str = "X-Cache";
+
+/// Most intervals a client can ask for with "next" or "previous", a week of 30min intervals.
+pub const MAX_WINDOW_INTERVALS: u32 = 336;
+
+/// Most responses kept in the cache, so clients asking for many different windows or dates can
+/// not grow it without limit. Once full, new keys are fetched without being cached.
+pub const MAX_CACHE_ENTRIES: usize = 1000;
+
+/// Struct type for a cached upstream response body.
+#[derive(Debug, Clone)]
+struct CachedResponse {
+    body: String,
+    expires_at: u64,
+}
+
+/// A cached response for one key, locked while it is fetched so each key has one upstream request.
+type CacheSlot = Arc<Mutex<Option<CachedResponse>>>;
+
+/// Struct type holding what the proxy needs to query Amber, and the responses it has cached.
+pub struct ProxyState {
+    pub base_url: String,
+    pub auth_token: SecretString,
+    pub site_id: String,
+    pub state: String,
+    cache: Mutex<HashMap<String, CacheSlot>>,
+}
+
+/// Build the price/renewables window from the "next" and "previous" query parameters.
+fn window_from_query(query: &HashMap<String, String>) -> Result<String, String> {
+    let mut window = "current".to_string();
+    let mut separator = '?';
+    for name in ["next", "previous"] {
+        if let Some(value) = query.get(name) {
+            let count: u32 = value
+                .parse()
+                .ok()
+                .filter(|count| *count <= MAX_WINDOW_INTERVALS)
+                .ok_or_else(|| {
+                    format!(
+                        "{} must be a number of intervals, up to {}",
+                        name, MAX_WINDOW_INTERVALS
+                    )
+                })?;
+            window.push_str(&format!("{}{}={}", separator, name, count));
+            separator = '&';
+        }
+    }
+    Ok(window)
+}
+
+/// Validate a yyyy-mm-dd date query parameter.
+fn date_from_query(query: &HashMap<String, String>, name: &str) -> Result<String, String> {
+    let value = query
+        .get(name)
+        .ok_or_else(|| format!("{} is required", name))?;
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map(|date| date.to_string())
+        .map_err(|_| format!("{} must be in the format yyyy-mm-dd", name))
+}
+
+fn json_response(status: StatusCode, body: String, cache: Option<&str>) -> Response<Body> {
+    let mut response = Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json");
+    if let Some(cache) = cache {
+        response = response.header(CACHE_HEADER, cache);
+    }
+    response
+        .body(Body::from(body))
+        .unwrap_or_else(|_| Response::new(Body::empty()))
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response<Body> {
+    let body = serde_json::json!({ "message": message }).to_string();
+    json_response(status, body, None)
+}
+
+impl ProxyState {
+    pub fn new(base_url: String, auth_token: SecretString, site_id: String, state: String) -> Self {
+        Self {
+            base_url,
+            auth_token,
+            site_id,
+            state,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Answer a request from the cache, or from Amber if it is not cached for this interval yet.
+    /// Only this key is locked while fetching, so concurrent clients of the same key share one
+    /// upstream request without holding up the others.
+    async fn cached(&self, key: String, route: Route) -> Response<Body> {
+        let now = unix_now();
+        let slot = {
+            let mut cache = self.cache.lock().await;
+            // Slots that are locked are being fetched, so are kept.
+            cache.retain(|_, slot| match slot.try_lock() {
+                Ok(cached) => cached
+                    .as_ref()
+                    .is_some_and(|cached| cached.expires_at > now),
+                Err(_) => true,
+            });
+            if !cache.contains_key(&key) && cache.len() >= MAX_CACHE_ENTRIES {
+                debug!("Cache is full, not caching {}", key);
+                CacheSlot::default()
+            } else {
+                cache.entry(key.clone()).or_default().clone()
+            }
+        };
+        let mut cached = slot.lock().await;
+        if let Some(hit) = cached.as_ref().filter(|cached| cached.expires_at > now) {
+            debug!("Cache hit for {}", key);
+            return json_response(StatusCode::OK, hit.body.clone(), Some("HIT"));
+        }
+
+        let route_expires_at = route.expires_at(now);
+        match self.fetch(route).await {
+            Ok(body) => {
+                *cached = Some(CachedResponse {
+                    body: body.clone(),
+                    expires_at: route_expires_at,
+                });
+                json_response(StatusCode::OK, body, Some("MISS"))
+            }
+            Err(upstream_error) => {
+                error!("Upstream request failed: {}", upstream_error);
+                error_response(StatusCode::BAD_GATEWAY, &upstream_error.to_string())
+            }
+        }
+    }
+
+    /// Query Amber for a route, returning the JSON body to send back to the client.
+    async fn fetch(&self, route: Route) -> Result<String> {
+        let base_url = self.base_url.clone();
+        let auth_token = self.auth_token.clone();
+        let body = match route {
+            Route::Sites => serde_json::to_string(&get_site_data(base_url, auth_token).await?)?,
+            Route::Prices(window) => serde_json::to_string(
+                &get_prices(base_url, auth_token, self.site_id.clone(), window).await?,
+            )?,
+            Route::Usage(start_date, end_date) => serde_json::to_string(
+                &get_usage_by_date(
+                    base_url,
+                    auth_token,
+                    self.site_id.clone(),
+                    start_date,
+                    end_date,
+                )
+                .await?,
+            )?,
+            Route::Renewables(window) => serde_json::to_string(
+                &get_renewables(base_url, auth_token, self.state.clone(), window).await?,
+            )?,
+        };
+        Ok(body)
+    }
+}
+
+/// Enum type for the upstream endpoints the proxy mirrors.
+#[derive(Debug)]
+enum Route {
+    Sites,
+    Prices(String),
+    Usage(String, String),
+    Renewables(String),
+}
+
+impl Route {
+    /// Unix time a response for this route expires at, the next interval boundary, or never for
+    /// usage of days that have ended, which is kept until the proxy exits.
+    fn expires_at(&self, now: u64) -> u64 {
+        match self {
+            Route::Usage(_, end_date) if usage_has_ended(end_date, now) => u64::MAX,
+            _ => next_interval_boundary(now),
+        }
+    }
+}
+
+/// Whether a yyyy-mm-dd usage end date is before today in NEM time.
+pub fn usage_has_ended(end_date: &str, now: u64) -> bool {
+    let nem_now = now as i64 + i64::from(NEM_UTC_OFFSET_HOURS) * 3600;
+    let today = Utc
+        .timestamp_opt(nem_now, 0)
+        .single()
+        .map(|now| now.date_naive());
+    NaiveDate::parse_from_str(end_date, "%Y-%m-%d")
+        .ok()
+        .zip(today)
+        .is_some_and(|(end_date, today)| end_date < today)
+}
+
+/// Handle a single proxy request.
+#[tracing::instrument(level = "debug", skip(state, request), fields(uri = %request.uri()))]
+pub async fn handle_request(state: Arc<ProxyState>, request: Request<Body>) -> Response<Body> {
+    if request.method() != Method::GET {
+        return error_response(StatusCode::METHOD_NOT_ALLOWED, "Only GET is supported");
+    }
+
+    // Only the path and query are used, the host is a placeholder to parse them.
+    let url = match Url::parse(&format!("http://localhost{}", request.uri())) {
+        Ok(url) => url,
+        Err(_) => return error_response(StatusCode::BAD_REQUEST, "Invalid request URI"),
+    };
+    let query: HashMap<String, String> = url.query_pairs().into_owned().collect();
+
+    let route = match url.path().trim_end_matches('/') {
+        "/sites" => Ok(Route::Sites),
+        "/prices" | "/prices/current" => window_from_query(&query).map(Route::Prices),
+        "/usage" => date_from_query(&query, "startDate").and_then(|start_date| {
+            date_from_query(&query, "endDate").map(|end_date| Route::Usage(start_date, end_date))
+        }),
+        "/renewables" | "/renewables/current" => window_from_query(&query).map(Route::Renewables),
+        _ => return error_response(StatusCode::NOT_FOUND, "Not found"),
+    };
+
+    match route {
+        Ok(route) => {
+            let key = format!("{:?}", route);
+            state.cached(key, route).await
+        }
+        Err(message) => error_response(StatusCode::BAD_REQUEST, &message),
+    }
+}
+
+/// Run the local proxy, serving cached copies of the Amber endpoints on `listen`.
+#[tracing::instrument(level = "debug", skip(auth_token))]
+pub async fn serve_proxy(
+    base_url: String,
+    auth_token: SecretString,
+    site_id: String,
+    state: String,
+    listen: SocketAddr,
+) -> Result<()> {
+    let proxy_state = Arc::new(ProxyState::new(base_url, auth_token, site_id, state));
+
+    let make_service = make_service_fn(move |_connection| {
+        let proxy_state = proxy_state.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                let proxy_state = proxy_state.clone();
+                async move { Ok::<_, Infallible>(handle_request(proxy_state, request).await) }
+            }))
+        }
+    });
+
+    info!("Serving Amber API proxy on http://{}", listen);
+    Server::try_bind(&listen)?.serve(make_service).await?;
+    Ok(())
+}
@@ -1,8 +1,13 @@
 use anyhow::Result;
 use iso8601_timestamp::Timestamp;
 use reqwest::Client;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use tracing::warn;
+
+use crate::cache::{self, CachePolicy, SITE_DETAILS_TTL_SECONDS};
+use crate::secret::SecretString;
 
 /// Struct type that matches the resulting data from the Amber "/sites" REST endpoint.
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
@@ -92,10 +97,10 @@ pub struct RenewablesData {
 }
 
 /// Struct type that provides options for our implementation of a reqwest client.
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct RestClient {
     pub url: String,
-    pub auth_token: String,
+    pub auth_token: SecretString,
     pub client: reqwest::Client,
 }
 
@@ -114,7 +119,7 @@ pub enum Error {
 
 /// Implementation of our client to interact with the Amber REST API endpoints.
 impl RestClient {
-    pub fn new_client(url: String, auth_token: String) -> Self {
+    pub fn new_client(url: String, auth_token: SecretString) -> Self {
         Self {
             url,
             auth_token,
@@ -122,9 +127,9 @@ impl RestClient {
         }
     }
 
-    /// RestClient function to request data from the Amber "/sites" endpoint.
-    pub async fn get_site_data(&mut self) -> Result<Vec<SiteDetails>, Error> {
-        let auth_token_header = format!("Bearer {}", &self.auth_token);
+    /// Send a GET request to the client's URL and return the body of a 200 response.
+    async fn get_body(&self) -> Result<String, Error> {
+        let auth_token_header = format!("Bearer {}", self.auth_token.expose_secret());
 
         let response = self
             .client
@@ -135,10 +140,7 @@ impl RestClient {
             .send()
             .await?;
         match response.status() {
-            reqwest::StatusCode::OK => {
-                let response = response.json::<Vec<SiteDetails>>().await?;
-                Ok(response)
-            }
+            reqwest::StatusCode::OK => Ok(response.text().await?),
             _ => Err(Error::HttpNon200Status {
                 status_code: (response.status().to_string()),
                 body: (response.text().await)?,
@@ -146,37 +148,65 @@ impl RestClient {
         }
     }
 
+    /// Request and decode the data at the client's URL, using the response cache when it is enabled.
+    /// `policy` decides how long a fresh response may be served from the cache.
+    async fn get_cached<T: DeserializeOwned>(
+        &self,
+        policy: impl Fn(&T) -> CachePolicy,
+    ) -> Result<T, Error> {
+        let cache = cache::global();
+        if let Some(body) =
+            cache.and_then(|cache| cache.get(&self.url, self.auth_token.expose_secret()))
+        {
+            match serde_json::from_str(&body) {
+                Ok(data) => return Ok(data),
+                Err(error) => warn!(
+                    "Ignoring unreadable cache entry for {}: {}",
+                    self.url, error
+                ),
+            }
+        }
+
+        let body = self.get_body().await?;
+        let data = serde_json::from_str(&body)?;
+        if let Some(cache) = cache {
+            if let Err(error) = cache.put(
+                &self.url,
+                self.auth_token.expose_secret(),
+                &body,
+                policy(&data),
+            ) {
+                warn!("Failed to cache response for {}: {}", self.url, error);
+            }
+        }
+        Ok(data)
+    }
+
+    /// RestClient function to request data from the Amber "/sites" endpoint.
+    pub async fn get_site_data(&mut self) -> Result<Vec<SiteDetails>, Error> {
+        self.get_cached(|_: &Vec<SiteDetails>| CachePolicy::Seconds(SITE_DETAILS_TTL_SECONDS))
+            .await
+    }
+
     /// RestClient function to request data from the Amber "/prices" endpoint.
     pub async fn get_price_data(&mut self) -> Result<Vec<PriceData>> {
-        let auth_token_header = format!("Bearer {}", &self.auth_token);
-
         let response = self
-            .client
-            .get(&self.url)
-            .header("AUTHORIZATION", auth_token_header)
-            .header("CONTENT_TYPE", "application/json")
-            .header("ACCEPT", "application/json")
-            .send()
-            .await?
-            .json::<Vec<PriceData>>()
+            .get_cached(|_: &Vec<PriceData>| CachePolicy::UntilNextInterval)
             .await?;
 
         Ok(response)
     }
 
     /// RestClient function to request data from the Amber "/usage" endpoint.
+    /// Usage that has been billed will not change, so it is cached forever.
     pub async fn get_usage_data(&mut self) -> Result<Vec<UsageData>> {
-        let auth_token_header = format!("Bearer {}", &self.auth_token);
-
         let response = self
-            .client
-            .get(&self.url)
-            .header("AUTHORIZATION", auth_token_header)
-            .header("CONTENT_TYPE", "application/json")
-            .header("ACCEPT", "application/json")
-            .send()
-            .await?
-            .json::<Vec<UsageData>>()
+            .get_cached(|usage: &Vec<UsageData>| {
+                match !usage.is_empty() && usage.iter().all(|data| data.quality == "billable") {
+                    true => CachePolicy::Forever,
+                    false => CachePolicy::UntilNextInterval,
+                }
+            })
             .await?;
 
         Ok(response)
@@ -184,17 +214,8 @@ impl RestClient {
 
     /// RustClient function to request data from the Amber "/renewables" endpoint.
     pub async fn get_renewables_data(&self) -> Result<Vec<RenewablesData>> {
-        let auth_token_header = format!("Bearer {}", &self.auth_token);
-
         let response = self
-            .client
-            .get(&self.url)
-            .header("AUTHORIZATION", auth_token_header)
-            .header("CONTENT_TYPE", "application/json")
-            .header("ACCEPT", "application/json")
-            .send()
-            .await?
-            .json::<Vec<RenewablesData>>()
+            .get_cached(|_: &Vec<RenewablesData>| CachePolicy::UntilNextInterval)
             .await?;
 
         Ok(response)
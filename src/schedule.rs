@@ -0,0 +1,556 @@
+// `map_or(true, ..)` rather than `is_none_or`, which needs Rust 1.82.
+#![allow(clippy::unnecessary_map_or)]
+
+use anyhow::{anyhow, bail, Context, Result};
+use config::{Config, File, FileFormat};
+use iso8601_timestamp::Timestamp;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use time::{Duration, OffsetDateTime, Time, UtcOffset};
+use tracing::debug;
+
+use crate::calendar::{format_ics_time, CalendarEvent};
+use crate::get_prices;
+use crate::planner::{
+    estimated_cost, forecast_intervals_for, group_runs, parse_duration_minutes,
+    plannable_intervals, PlanRun,
+};
+use crate::rest_client::PriceData;
+use crate::secret::SecretString;
+
+/// Clock times in the loads file ("HH:MM") are NEM time, UTC+10, the same as Amber's `nemTime`.
+pub const NEM_UTC_OFFSET_HOURS: i8 = 10;
+
+/// Struct type for a flexible load in the loads file, e.g.
+///
+/// ```toml
+/// [[load]]
+/// name = "dishwasher"
+/// power_kw = 1.8
+/// duration = "2h"
+/// earliest_start = "09:00"
+/// deadline = "17:00"
+/// interruptible = false
+/// ```
+#[derive(Deserialize, Debug, Clone)]
+pub struct FlexibleLoad {
+    pub name: String,
+    pub power_kw: f32,
+    /// How long the load runs for, e.g. 2h, 90m or 1h30m.
+    pub duration: String,
+    /// "HH:MM" in NEM time or a full ISO 8601 timestamp, defaults to now.
+    pub earliest_start: Option<String>,
+    /// "HH:MM" in NEM time or a full ISO 8601 timestamp, defaults to the end of the forecast.
+    pub deadline: Option<String>,
+    /// Whether the load can be paused and resumed, such as a pool pump or EV charger.
+    #[serde(default)]
+    pub interruptible: bool,
+}
+
+/// Struct type for the loads file.
+#[derive(Deserialize, Debug, Clone)]
+pub struct LoadsFile {
+    /// Limit on the combined draw of loads running at the same time.
+    pub max_power_kw: Option<f32>,
+    #[serde(rename = "load", default)]
+    pub loads: Vec<FlexibleLoad>,
+}
+
+impl LoadsFile {
+    /// Function to read a loads file in TOML format.
+    pub fn read(loads_file: &str) -> Result<Self> {
+        let loads: LoadsFile = Config::builder()
+            .add_source(File::new(loads_file, FileFormat::Toml))
+            .build()?
+            .try_deserialize()
+            .with_context(|| format!("Failed to read loads file {}", loads_file))?;
+        if loads.loads.is_empty() {
+            bail!("No [[load]] sections in {}", loads_file);
+        }
+        Ok(loads)
+    }
+}
+
+/// Struct type for a load with its duration and time window resolved.
+#[derive(Debug, Clone)]
+pub struct ResolvedLoad {
+    pub name: String,
+    pub power_kw: f32,
+    pub duration_minutes: u32,
+    pub earliest_start: Option<Timestamp>,
+    pub deadline: Option<Timestamp>,
+    pub interruptible: bool,
+}
+
+/// Struct type for when a load should run.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledLoad {
+    pub name: String,
+    pub power_kw: f32,
+    pub start_time: Timestamp,
+    pub end_time: Timestamp,
+    pub runs: Vec<PlanRun>,
+    pub average_per_kwh: f32,
+    /// Estimated cost in cents.
+    pub estimated_cost: f32,
+}
+
+/// Struct type for a schedule covering every load in the loads file.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadSchedule {
+    pub channel_type: String,
+    pub loads: Vec<ScheduledLoad>,
+    /// Estimated cost of every load in cents.
+    pub total_cost: f32,
+}
+
+/// Parse a "HH:MM" clock time.
+pub fn parse_clock_time(value: &str) -> Result<Time> {
+    let invalid = || anyhow!("Invalid time {}, use HH:MM", value);
+    let (hour, minute) = value.split_once(':').ok_or_else(invalid)?;
+    Time::from_hms(
+        hour.parse().map_err(|_| invalid())?,
+        minute.parse().map_err(|_| invalid())?,
+        0,
+    )
+    .map_err(|_| invalid())
+}
+
+/// Resolve a "HH:MM" NEM time to its next occurrence at or after `after`,
+/// or parse a full ISO 8601 timestamp.
+pub fn resolve_time(value: &str, after: &Timestamp) -> Result<Timestamp> {
+    if let Some(timestamp) = Timestamp::parse(value) {
+        return Ok(timestamp);
+    }
+
+    let clock = parse_clock_time(value)
+        .map_err(|_| anyhow!("Invalid time {}, use HH:MM or an ISO 8601 timestamp", value))?;
+
+    let after_nem = to_nem_time(after);
+    let mut resolved = after_nem.replace_time(clock);
+    if resolved < after_nem {
+        resolved += Duration::days(1);
+    }
+    Ok(Timestamp::from(resolved.to_offset(UtcOffset::UTC)))
+}
+
+/// Convert a UTC timestamp to NEM time.
+pub fn to_nem_time(timestamp: &Timestamp) -> OffsetDateTime {
+    let nem_offset = UtcOffset::from_hms(NEM_UTC_OFFSET_HOURS, 0, 0).unwrap_or(UtcOffset::UTC);
+    timestamp.assume_utc().to_offset(nem_offset)
+}
+
+/// Format a timestamp as a "HH:MM" NEM time, dropping the second Amber intervals start on.
+pub fn nem_clock_time(timestamp: &Timestamp) -> String {
+    let nem_time = to_nem_time(timestamp);
+    format!("{:02}:{:02}", nem_time.hour(), nem_time.minute())
+}
+
+impl FlexibleLoad {
+    /// Resolve the duration and time window, "HH:MM" times are the next occurrence after `now`
+    /// and a "HH:MM" deadline is the next occurrence after the earliest start.
+    pub fn resolve(&self, now: &Timestamp) -> Result<ResolvedLoad> {
+        let earliest_start = self
+            .earliest_start
+            .as_deref()
+            .map(|value| resolve_time(value, now))
+            .transpose()?;
+        let deadline = self
+            .deadline
+            .as_deref()
+            .map(|value| resolve_time(value, earliest_start.as_ref().unwrap_or(now)))
+            .transpose()?;
+        if self.power_kw <= 0.0 {
+            bail!("Load {} must have a power_kw above zero", self.name);
+        }
+        Ok(ResolvedLoad {
+            name: self.name.clone(),
+            power_kw: self.power_kw,
+            duration_minutes: parse_duration_minutes(&self.duration)?,
+            earliest_start,
+            deadline,
+            interruptible: self.interruptible,
+        })
+    }
+}
+
+/// Indexes of the intervals a load is allowed to run in.
+fn allowed_intervals(load: &ResolvedLoad, intervals: &[&PriceData]) -> Vec<bool> {
+    intervals
+        .iter()
+        .map(|interval| {
+            // Amber intervals start one second past the half hour.
+            load.earliest_start.map_or(true, |earliest| {
+                interval.start_time >= earliest - Duration::seconds(1)
+            }) && load
+                .deadline
+                .map_or(true, |deadline| interval.end_time <= deadline)
+        })
+        .collect()
+}
+
+/// Whether a load fits inside its allowed intervals when nothing else is running.
+fn fits_alone(load: &ResolvedLoad, allowed: &[bool], needed: usize) -> bool {
+    if load.interruptible {
+        allowed.iter().filter(|a| **a).count() >= needed
+    } else {
+        allowed.split(|a| !*a).any(|window| window.len() >= needed)
+    }
+}
+
+/// Most loads the exact search schedules at once, each load is a byte of the search state.
+const MAX_EXACT_LOADS: usize = 16;
+
+/// Most search steps (intervals × states × combinations of loads) the exact search is used for,
+/// larger schedules are placed greedily so they finish in well under a second.
+const MAX_EXACT_STEPS: u64 = 2_000_000;
+
+/// Whether the exact search is small enough to run, from an upper bound on its search steps.
+fn exact_search_fits(intervals: usize, needed: &[usize]) -> bool {
+    if needed.len() > MAX_EXACT_LOADS || needed.iter().any(|n| *n > u8::MAX as usize) {
+        return false;
+    }
+    let steps = needed.iter().try_fold(intervals as u64, |steps, needed| {
+        steps.checked_mul(2 * (*needed as u64 + 1))
+    });
+    steps.is_some_and(|steps| steps <= MAX_EXACT_STEPS)
+}
+
+/// Place loads one at a time, the load with the fewest allowed intervals to spare first,
+/// each taking the cheapest intervals still under `max_power_kw`.
+fn greedy_intervals(
+    intervals: &[&PriceData],
+    loads: &[ResolvedLoad],
+    allowed: &[Vec<bool>],
+    needed: &[usize],
+    max_power_kw: Option<f32>,
+) -> Option<Vec<Vec<usize>>> {
+    let mut order: Vec<usize> = (0..loads.len()).collect();
+    order.sort_by_key(|load| {
+        allowed[*load]
+            .iter()
+            .filter(|a| **a)
+            .count()
+            .saturating_sub(needed[*load])
+    });
+
+    let mut power_used = vec![0.0_f32; intervals.len()];
+    let mut chosen = vec![Vec::new(); loads.len()];
+    for load in order {
+        let power_kw = loads[load].power_kw;
+        let available: Vec<bool> = allowed[load]
+            .iter()
+            .zip(&power_used)
+            .map(|(allowed, used)| {
+                *allowed && max_power_kw.map_or(true, |max| used + power_kw <= max)
+            })
+            .collect();
+        let needed = needed[load];
+        let load_chosen: Vec<usize> = if loads[load].interruptible {
+            let mut ranked: Vec<usize> = (0..intervals.len()).filter(|i| available[*i]).collect();
+            if ranked.len() < needed {
+                return None;
+            }
+            ranked.sort_by(|a, b| intervals[*a].per_kwh.total_cmp(&intervals[*b].per_kwh));
+            let mut ranked: Vec<usize> = ranked.into_iter().take(needed).collect();
+            ranked.sort();
+            ranked
+        } else {
+            let window_cost = |start: usize| -> f32 {
+                intervals[start..start + needed]
+                    .iter()
+                    .map(|interval| interval.per_kwh)
+                    .sum()
+            };
+            let start = (0..=intervals.len().checked_sub(needed)?)
+                .filter(|start| available[*start..*start + needed].iter().all(|a| *a))
+                .min_by(|a, b| window_cost(*a).total_cmp(&window_cost(*b)))?;
+            (start..start + needed).collect()
+        };
+        for i in &load_chosen {
+            power_used[*i] += power_kw;
+        }
+        chosen[load] = load_chosen;
+    }
+    Some(chosen)
+}
+
+/// Intervals still needed by each load, one byte per load.
+type Remaining = u128;
+
+fn remaining_of(remaining: Remaining, load: usize) -> usize {
+    ((remaining >> (8 * load)) & 0xff) as usize
+}
+
+/// Struct type for the exact search over which loads run in each interval.
+///
+/// The state at each interval is how many intervals every load still needs, a load that can not
+/// be paused is running when it has started but not finished. Every combination of loads that
+/// can run in an interval is tried, and the cheapest way to finish from each state is memoised.
+struct ScheduleSearch<'a> {
+    intervals: &'a [&'a PriceData],
+    loads: &'a [ResolvedLoad],
+    allowed: Vec<Vec<bool>>,
+    needed: Vec<usize>,
+    /// Allowed intervals from each interval to the end of the forecast, per load.
+    allowed_left: Vec<Vec<usize>>,
+    max_power_kw: Option<f32>,
+    /// Cheapest cost to finish from an interval and state, with the loads to run in it.
+    memo: HashMap<(usize, Remaining), Option<(f32, u32)>>,
+}
+
+impl<'a> ScheduleSearch<'a> {
+    fn new(
+        intervals: &'a [&'a PriceData],
+        loads: &'a [ResolvedLoad],
+        allowed: Vec<Vec<bool>>,
+        needed: Vec<usize>,
+        max_power_kw: Option<f32>,
+    ) -> Self {
+        let allowed_left = allowed
+            .iter()
+            .map(|allowed| {
+                let mut left = vec![0; allowed.len() + 1];
+                for i in (0..allowed.len()).rev() {
+                    left[i] = left[i + 1] + usize::from(allowed[i]);
+                }
+                left
+            })
+            .collect();
+        Self {
+            intervals,
+            loads,
+            allowed,
+            needed,
+            allowed_left,
+            max_power_kw,
+            memo: HashMap::new(),
+        }
+    }
+
+    /// State at the start of the forecast, before any load has run.
+    fn start(&self) -> Remaining {
+        self.needed
+            .iter()
+            .enumerate()
+            .fold(0, |state, (load, needed)| {
+                state | (*needed as Remaining) << (8 * load)
+            })
+    }
+
+    /// Cheapest cost in cents of finishing every load from interval `index` onwards.
+    fn cheapest(&mut self, index: usize, remaining: Remaining) -> Option<f32> {
+        if remaining == 0 {
+            return Some(0.0);
+        }
+        let loads = self.loads.len();
+        if (0..loads).any(|load| remaining_of(remaining, load) > self.allowed_left[load][index]) {
+            return None;
+        }
+        if let Some(best) = self.memo.get(&(index, remaining)) {
+            return best.map(|(cost, _)| cost);
+        }
+
+        let mut can_run = 0u32;
+        let mut must_run = 0u32;
+        for load in 0..loads {
+            let left = remaining_of(remaining, load);
+            if left > 0 && self.allowed[load][index] {
+                can_run |= 1 << load;
+            }
+            if !self.loads[load].interruptible && left > 0 && left < self.needed[load] {
+                must_run |= 1 << load;
+            }
+        }
+
+        let mut best: Option<(f32, u32)> = None;
+        if must_run & !can_run == 0 {
+            // Every subset of the optional loads, on top of the loads that have to keep running.
+            let optional = can_run & !must_run;
+            let mut subset = optional;
+            loop {
+                let running = subset | must_run;
+                if let Some(cost) = self.run(index, remaining, running) {
+                    if best.map_or(true, |(best_cost, _)| cost < best_cost) {
+                        best = Some((cost, running));
+                    }
+                }
+                if subset == 0 {
+                    break;
+                }
+                subset = (subset - 1) & optional;
+            }
+        }
+
+        self.memo.insert((index, remaining), best);
+        best.map(|(cost, _)| cost)
+    }
+
+    /// Cost of running the loads in `running` in interval `index`, then finishing the cheapest way.
+    fn run(&mut self, index: usize, remaining: Remaining, running: u32) -> Option<f32> {
+        let mut power_kw = 0.0;
+        let mut next = remaining;
+        for load in 0..self.loads.len() {
+            if running & (1 << load) != 0 {
+                power_kw += self.loads[load].power_kw;
+                next -= 1 << (8 * load);
+            }
+        }
+        if self.max_power_kw.is_some_and(|max| power_kw > max) {
+            return None;
+        }
+        let cost = estimated_cost(&[self.intervals[index]], power_kw);
+        Some(cost + self.cheapest(index + 1, next)?)
+    }
+
+    /// The intervals each load runs in, following the memoised cheapest choices.
+    fn chosen_intervals(&mut self) -> Option<Vec<Vec<usize>>> {
+        let mut remaining = self.start();
+        self.cheapest(0, remaining)?;
+        let mut chosen = vec![Vec::new(); self.loads.len()];
+        for index in 0..self.intervals.len() {
+            if remaining == 0 {
+                break;
+            }
+            let (_, running) = (*self.memo.get(&(index, remaining))?)?;
+            for (load, chosen) in chosen.iter_mut().enumerate() {
+                if running & (1 << load) != 0 {
+                    chosen.push(index);
+                    remaining -= 1 << (8 * load);
+                }
+            }
+        }
+        Some(chosen)
+    }
+}
+
+/// Schedule every load at the lowest total cost in the forecast.
+///
+/// An exact search over which loads run in each interval, so loads competing for the same cheap
+/// intervals under `max_power_kw` are shared out at the lowest total cost. Schedules too large to
+/// search are placed greedily instead.
+pub fn schedule_loads(
+    prices: &[PriceData],
+    channel_type: &str,
+    loads: &[ResolvedLoad],
+    max_power_kw: Option<f32>,
+) -> Result<LoadSchedule> {
+    let intervals = plannable_intervals(prices, channel_type, u32::MAX);
+    let interval_minutes = match intervals.first() {
+        Some(interval) => u32::from(interval.duration),
+        None => bail!("No price forecast for the {} channel", channel_type),
+    };
+    let mut allowed = Vec::new();
+    let mut needed = Vec::new();
+    for load in loads {
+        let load_allowed = allowed_intervals(load, &intervals);
+        let load_needed = load.duration_minutes.div_ceil(interval_minutes) as usize;
+        let fits_power = max_power_kw.map_or(true, |max| load.power_kw <= max);
+        if !fits_power || !fits_alone(load, &load_allowed, load_needed) {
+            bail!(
+                "Could not fit load {} ({} intervals) inside its time window in the forecast",
+                load.name,
+                load_needed
+            );
+        }
+        allowed.push(load_allowed);
+        needed.push(load_needed);
+    }
+
+    let chosen = if exact_search_fits(intervals.len(), &needed) {
+        ScheduleSearch::new(&intervals, loads, allowed, needed, max_power_kw).chosen_intervals()
+    } else {
+        debug!("Schedule is too large for the exact search, placing loads greedily");
+        greedy_intervals(&intervals, loads, &allowed, &needed, max_power_kw)
+    }
+    .ok_or_else(|| {
+        anyhow!("Could not fit every load inside its time window and under max_power_kw")
+    })?;
+
+    let loads: Vec<ScheduledLoad> = loads
+        .iter()
+        .zip(chosen)
+        .map(|(load, chosen)| {
+            let chosen: Vec<&PriceData> = chosen.iter().map(|i| intervals[*i]).collect();
+            let runs = group_runs(&chosen);
+            ScheduledLoad {
+                name: load.name.clone(),
+                power_kw: load.power_kw,
+                start_time: runs[0].start_time,
+                end_time: runs[runs.len() - 1].end_time,
+                runs,
+                average_per_kwh: chosen.iter().map(|i| i.per_kwh).sum::<f32>()
+                    / chosen.len() as f32,
+                estimated_cost: estimated_cost(&chosen, load.power_kw),
+            }
+        })
+        .collect();
+    Ok(LoadSchedule {
+        channel_type: channel_type.to_string(),
+        total_cost: loads.iter().map(|load| load.estimated_cost).sum(),
+        loads,
+    })
+}
+
+impl LoadSchedule {
+    /// One calendar event per run of each load, the UID is the load and the date of its run,
+    /// so re-importing an updated schedule moves the event instead of adding another.
+    pub fn calendar_events(&self) -> Vec<CalendarEvent> {
+        let mut events = Vec::new();
+        for load in &self.loads {
+            let slug: String = load
+                .name
+                .to_lowercase()
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+                .collect();
+            for (run_number, run) in load.runs.iter().enumerate() {
+                events.push(CalendarEvent {
+                    uid: format!(
+                        "plan-{}-{}-{}@amber-cli",
+                        slug,
+                        &format_ics_time(&run.start_time)[..8],
+                        run_number + 1
+                    ),
+                    start_time: run.start_time,
+                    end_time: run.end_time,
+                    summary: format!("Run {}", load.name),
+                    description: Some(format!(
+                        "{} kW, average {:.2}c/kWh, estimated cost {:.2}c",
+                        load.power_kw, load.average_per_kwh, load.estimated_cost
+                    )),
+                });
+            }
+        }
+        events
+    }
+}
+
+/// Function to fetch the forecast and schedule every load in the loads file.
+#[tracing::instrument(level = "debug", skip(auth_token, loads_file))]
+pub async fn plan_schedule(
+    base_url: String,
+    auth_token: SecretString,
+    site_id: String,
+    channel_type: String,
+    within_minutes: u32,
+    loads_file: LoadsFile,
+) -> Result<LoadSchedule> {
+    let now = Timestamp::now_utc();
+    let loads = loads_file
+        .loads
+        .iter()
+        .map(|load| load.resolve(&now))
+        .collect::<Result<Vec<ResolvedLoad>>>()?;
+
+    let window = format!("current?next={}", forecast_intervals_for(within_minutes));
+    let forecast_price_data = get_prices(base_url, auth_token, site_id, window).await?;
+    schedule_loads(
+        &forecast_price_data,
+        &channel_type,
+        &loads,
+        loads_file.max_power_kw,
+    )
+}
@@ -0,0 +1,125 @@
+use anyhow::{bail, Result};
+use iso8601_timestamp::Timestamp;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::bill::{channel_totals, parse_report_date, ChannelTotals, FEED_IN_CHANNEL};
+use crate::get_usage_by_date;
+use crate::rest_client::UsageData;
+use crate::schedule::to_nem_time;
+use crate::secret::SecretString;
+
+/// Struct type for an interval where exporting solar cost money.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct NegativeExport {
+    pub start_time: Timestamp,
+    pub end_time: Timestamp,
+    pub kwh: f32,
+    /// Amber's feed in price, a cost, so above zero when exporting costs money.
+    pub per_kwh: f32,
+    pub cost: f32,
+}
+
+/// Struct type for how often exporting cost money in an hour of the day, in NEM time.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct NegativeHour {
+    pub hour: String,
+    pub intervals: usize,
+    pub kwh: f32,
+    pub cost: f32,
+}
+
+/// Struct type for the solar export report, costs are in cents.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SolarReport {
+    pub channels: Vec<ChannelTotals>,
+    pub export_kwh: f32,
+    /// What exporting earned, less what it cost in negative price intervals.
+    pub export_earnings: f32,
+    pub negative_intervals: Vec<NegativeExport>,
+    pub negative_kwh: f32,
+    /// What exporting cost in negative price intervals.
+    pub negative_cost: f32,
+    /// Hours of the day that exporting cost money, worst first, the times to curtail the inverter.
+    pub curtail_hours: Vec<NegativeHour>,
+}
+
+/// Split usage by channel and find the intervals where exporting cost money.
+pub fn build_solar_report(usage: &[UsageData]) -> SolarReport {
+    let feed_in: Vec<&UsageData> = usage
+        .iter()
+        .filter(|record| record.channel_type == FEED_IN_CHANNEL)
+        .collect();
+
+    // Amber's feed in price is a cost, so a price above zero means exporting cost money.
+    let mut negative_intervals: Vec<NegativeExport> = feed_in
+        .iter()
+        .filter(|record| record.per_kwh > 0.0 && record.kwh > 0.0)
+        .map(|record| NegativeExport {
+            start_time: record.start_time,
+            end_time: record.end_time,
+            kwh: record.kwh,
+            per_kwh: record.per_kwh,
+            cost: record.cost,
+        })
+        .collect();
+    negative_intervals.sort_by_key(|interval| interval.start_time);
+
+    let mut hours: BTreeMap<String, NegativeHour> = BTreeMap::new();
+    for interval in &negative_intervals {
+        let hour = format!("{:02}:00", to_nem_time(&interval.start_time).hour());
+        let totals = hours.entry(hour.clone()).or_insert_with(|| NegativeHour {
+            hour,
+            intervals: 0,
+            kwh: 0.0,
+            cost: 0.0,
+        });
+        totals.intervals += 1;
+        totals.kwh += interval.kwh;
+        totals.cost += interval.cost;
+    }
+    let mut curtail_hours: Vec<NegativeHour> = hours.into_values().collect();
+    curtail_hours.sort_by(|a, b| b.cost.total_cmp(&a.cost));
+
+    SolarReport {
+        channels: channel_totals(usage),
+        export_kwh: feed_in.iter().map(|record| record.kwh).sum(),
+        export_earnings: -feed_in.iter().map(|record| record.cost).sum::<f32>(),
+        negative_kwh: negative_intervals.iter().map(|interval| interval.kwh).sum(),
+        negative_cost: negative_intervals
+            .iter()
+            .map(|interval| interval.cost)
+            .sum(),
+        negative_intervals,
+        curtail_hours,
+    }
+}
+
+/// Function to fetch usage for a date range and report on solar export.
+#[tracing::instrument(level = "debug", skip(auth_token))]
+pub async fn get_solar_report(
+    base_url: String,
+    auth_token: SecretString,
+    site_id: String,
+    start_date: String,
+    end_date: String,
+) -> Result<SolarReport> {
+    if parse_report_date(&end_date)? < parse_report_date(&start_date)? {
+        bail!(
+            "End date {} is before the start date {}",
+            end_date,
+            start_date
+        );
+    }
+    let usage = get_usage_by_date(base_url, auth_token, site_id, start_date, end_date).await?;
+    if !usage
+        .iter()
+        .any(|record| record.channel_type == FEED_IN_CHANNEL)
+    {
+        bail!("No feedIn usage for this site, it has no solar export to report on");
+    }
+    Ok(build_solar_report(&usage))
+}
@@ -0,0 +1,89 @@
+use iso8601_timestamp::Timestamp;
+use serde::Serialize;
+
+use crate::rest_client::PriceData;
+
+/// Interval type Amber uses for forecast price data.
+pub const FORECAST_INTERVAL: &str = "ForecastInterval";
+
+/// Struct type that describes the first run of spiking intervals found in a price forecast.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SpikeForecast {
+    pub channel_type: String,
+    pub start_time: Timestamp,
+    pub end_time: Timestamp,
+    pub duration_minutes: u32,
+    pub intervals: usize,
+    pub spike_status: String,
+    pub max_per_kwh: f32,
+}
+
+impl SpikeForecast {
+    /// Human readable summary of the forecast spike, used by the CLI and watch mode alerts.
+    pub fn summary(&self) -> String {
+        format!(
+            "Spike ({}) expected from {} to {}, lasting {} minutes with a peak of {:.2} c/kWh on the {} channel",
+            self.spike_status,
+            self.start_time,
+            self.end_time,
+            self.duration_minutes,
+            self.max_per_kwh,
+            self.channel_type
+        )
+    }
+}
+
+/// Returns true when an interval should be treated as spiking.
+/// Either Amber flags it as a (potential) spike, or the price is above the users limit.
+pub fn is_spiking(interval: &PriceData, price_limit: Option<f32>) -> bool {
+    let flagged = matches!(interval.spike_status.as_str(), "potential" | "spike");
+    let over_limit = price_limit.is_some_and(|limit| interval.per_kwh > limit);
+    flagged || over_limit
+}
+
+/// Scan the forecast intervals of a channel and return the first run of spiking intervals.
+/// Consecutive spiking intervals are merged, so the result covers how long the spike lasts.
+pub fn find_forecast_spike(
+    prices: &[PriceData],
+    channel_type: &str,
+    price_limit: Option<f32>,
+) -> Option<SpikeForecast> {
+    let mut forecast: Vec<&PriceData> = prices
+        .iter()
+        .filter(|interval| {
+            interval.interval_type == FORECAST_INTERVAL && interval.channel_type == channel_type
+        })
+        .collect();
+    forecast.sort_by_key(|interval| interval.start_time);
+
+    let first_spike = forecast
+        .iter()
+        .position(|interval| is_spiking(interval, price_limit))?;
+
+    let spike_run: Vec<&PriceData> = forecast[first_spike..]
+        .iter()
+        .take_while(|interval| is_spiking(interval, price_limit))
+        .copied()
+        .collect();
+
+    // "spike" outranks "potential", anything else means only the price limit was crossed.
+    let spike_status = if spike_run.iter().any(|i| i.spike_status == "spike") {
+        "spike"
+    } else if spike_run.iter().any(|i| i.spike_status == "potential") {
+        "potential"
+    } else {
+        "priceLimit"
+    };
+
+    let last = spike_run.last()?;
+    Some(SpikeForecast {
+        channel_type: channel_type.to_string(),
+        start_time: spike_run[0].start_time,
+        end_time: last.end_time,
+        duration_minutes: spike_run.iter().map(|i| u32::from(i.duration)).sum(),
+        intervals: spike_run.len(),
+        spike_status: spike_status.to_string(),
+        max_per_kwh: spike_run.iter().map(|i| i.per_kwh).fold(f32::MIN, f32::max),
+    })
+}
@@ -0,0 +1,286 @@
+use anyhow::{anyhow, bail, Context, Result};
+use config::{Config, File, FileFormat};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use time::{Time, Weekday};
+
+use crate::app_config::BillingConfig;
+use crate::bill::{parse_report_date, DAYS_PER_MONTH, FEED_IN_CHANNEL};
+use crate::get_usage_by_date;
+use crate::rest_client::UsageData;
+use crate::schedule::{parse_clock_time, to_nem_time};
+use crate::secret::SecretString;
+
+/// Channel Amber reports controlled load (e.g. off-peak hot water) usage on.
+pub const CONTROLLED_LOAD_CHANNEL: &str = "controlledLoad";
+
+/// Struct type for a time of use window in a retail plan, e.g.
+///
+/// ```toml
+/// [[plan.period]]
+/// name = "peak"
+/// rate = 45.0
+/// start = "15:00"
+/// end = "21:00"
+/// days = ["weekdays"]
+/// ```
+#[derive(Deserialize, Debug, Clone)]
+pub struct TariffPeriod {
+    pub name: String,
+    /// Price in c/kWh.
+    pub rate: f32,
+    /// "HH:MM" in NEM time, the window wraps past midnight when `end` is before `start`.
+    pub start: String,
+    pub end: String,
+    /// "mon" to "sun", "weekdays", "weekends" or "all", defaults to every day.
+    #[serde(default)]
+    pub days: Vec<String>,
+}
+
+/// Struct type for a retail plan to compare against, all prices in cents.
+#[derive(Deserialize, Debug, Clone)]
+pub struct RetailPlan {
+    pub name: String,
+    /// Supply charge in c/day.
+    #[serde(default)]
+    pub daily_supply_charge: f32,
+    /// Flat rate in c/kWh, used for general usage outside every time of use period.
+    pub general_rate: Option<f32>,
+    #[serde(rename = "period", default)]
+    pub periods: Vec<TariffPeriod>,
+    /// Controlled load rate in c/kWh, controlled load is charged as general usage when not set.
+    pub controlled_load_rate: Option<f32>,
+    /// Extra supply charge in c/day for days with controlled load usage.
+    #[serde(default)]
+    pub controlled_load_supply_charge: f32,
+    /// Feed in tariff in c/kWh.
+    #[serde(default)]
+    pub feed_in_tariff: f32,
+}
+
+/// Struct type for the tariffs file.
+#[derive(Deserialize, Debug, Clone)]
+pub struct TariffsFile {
+    #[serde(rename = "plan", default)]
+    pub plans: Vec<RetailPlan>,
+}
+
+impl TariffsFile {
+    /// Function to read and check a tariffs file in TOML format.
+    pub fn read(tariffs_file: &str) -> Result<Self> {
+        let tariffs: TariffsFile = Config::builder()
+            .add_source(File::new(tariffs_file, FileFormat::Toml))
+            .build()?
+            .try_deserialize()
+            .with_context(|| format!("Failed to read tariffs file {}", tariffs_file))?;
+        if tariffs.plans.is_empty() {
+            bail!("No [[plan]] sections in {}", tariffs_file);
+        }
+        for plan in &tariffs.plans {
+            plan.validate()?;
+        }
+        Ok(tariffs)
+    }
+}
+
+/// Whether a day in a period's `days` list covers the weekday.
+fn day_matches(day: &str, weekday: Weekday) -> Result<bool> {
+    let weekend = matches!(weekday, Weekday::Saturday | Weekday::Sunday);
+    Ok(match day.to_lowercase().as_str() {
+        "all" => true,
+        "weekdays" => !weekend,
+        "weekends" => weekend,
+        "mon" => weekday == Weekday::Monday,
+        "tue" => weekday == Weekday::Tuesday,
+        "wed" => weekday == Weekday::Wednesday,
+        "thu" => weekday == Weekday::Thursday,
+        "fri" => weekday == Weekday::Friday,
+        "sat" => weekday == Weekday::Saturday,
+        "sun" => weekday == Weekday::Sunday,
+        _ => bail!(
+            "Invalid day {}, use mon to sun, weekdays, weekends or all",
+            day
+        ),
+    })
+}
+
+impl TariffPeriod {
+    /// Whether an interval starting at `time` on `weekday`, in NEM time, falls in this period.
+    pub fn contains(&self, weekday: Weekday, time: Time) -> Result<bool> {
+        let mut on_day = self.days.is_empty();
+        for day in &self.days {
+            on_day |= day_matches(day, weekday)?;
+        }
+        let start = parse_clock_time(&self.start)?;
+        let end = parse_clock_time(&self.end)?;
+        let in_window = if start < end {
+            time >= start && time < end
+        } else {
+            time >= start || time < end
+        };
+        Ok(on_day && in_window)
+    }
+}
+
+impl RetailPlan {
+    /// Check every period parses and general usage always has a rate.
+    pub fn validate(&self) -> Result<()> {
+        for period in &self.periods {
+            period
+                .contains(Weekday::Monday, Time::MIDNIGHT)
+                .with_context(|| format!("In period {} of plan {}", period.name, self.name))?;
+        }
+        if self.general_rate.is_none() && self.periods.is_empty() {
+            bail!(
+                "Plan {} needs a general_rate or at least one [[plan.period]]",
+                self.name
+            );
+        }
+        Ok(())
+    }
+
+    /// Rate in c/kWh for general usage in an interval.
+    fn general_rate_for(&self, record: &UsageData) -> Result<f32> {
+        // The second Amber intervals start on is dropped, so 15:00:01 is in a window starting 15:00.
+        let nem_time = to_nem_time(&record.start_time);
+        let time = Time::from_hms(nem_time.hour(), nem_time.minute(), 0)?;
+        for period in &self.periods {
+            if period.contains(nem_time.weekday(), time)? {
+                return Ok(period.rate);
+            }
+        }
+        self.general_rate.ok_or_else(|| {
+            anyhow!(
+                "Plan {} has no rate for {} at {}, add a general_rate",
+                self.name,
+                nem_time.weekday(),
+                time
+            )
+        })
+    }
+
+    /// Usage cost of an interval on this plan in cents, negative for feed in credit.
+    pub fn usage_cost(&self, record: &UsageData) -> Result<f32> {
+        let rate = match record.channel_type.as_str() {
+            FEED_IN_CHANNEL => -self.feed_in_tariff,
+            CONTROLLED_LOAD_CHANNEL => match self.controlled_load_rate {
+                Some(rate) => rate,
+                None => self.general_rate_for(record)?,
+            },
+            _ => self.general_rate_for(record)?,
+        };
+        Ok(record.kwh * rate)
+    }
+}
+
+/// Struct type for a month of a plan compared to Amber.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MonthComparison {
+    pub month: String,
+    pub days: usize,
+    /// What Amber charged, plus the fixed charges from the [billing] config, in cents.
+    pub amber_cost: f32,
+    pub plan_cost: f32,
+    /// Plan cost less Amber cost, positive when Amber was cheaper.
+    pub difference: f32,
+}
+
+/// Struct type for a plan compared to Amber over the whole date range.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanComparison {
+    pub name: String,
+    pub amber_cost: f32,
+    pub plan_cost: f32,
+    pub difference: f32,
+    pub months: Vec<MonthComparison>,
+}
+
+/// Struct type for running totals of one month.
+#[derive(Debug, Default)]
+struct MonthTotals {
+    days: BTreeSet<String>,
+    controlled_load_days: BTreeSet<String>,
+    amber_usage_cost: f32,
+    plan_usage_cost: f32,
+}
+
+/// Replay usage against a retail plan, month by month, and compare it to what Amber charged.
+pub fn compare_plan(
+    usage: &[UsageData],
+    plan: &RetailPlan,
+    billing: Option<&BillingConfig>,
+) -> Result<PlanComparison> {
+    let mut months: BTreeMap<String, MonthTotals> = BTreeMap::new();
+    for record in usage {
+        // Amber's `date` is the NEM date of the interval.
+        let date = record.date.date();
+        let totals = months
+            .entry(format!("{}-{:02}", date.year(), u8::from(date.month())))
+            .or_default();
+        totals.days.insert(date.to_string());
+        if record.channel_type == CONTROLLED_LOAD_CHANNEL {
+            totals.controlled_load_days.insert(date.to_string());
+        }
+        totals.amber_usage_cost += record.cost;
+        totals.plan_usage_cost += plan.usage_cost(record)?;
+    }
+
+    let amber_daily_charge = billing.map_or(0.0, |billing| {
+        billing.daily_supply_charge + billing.monthly_membership_fee / DAYS_PER_MONTH
+    });
+    let months: Vec<MonthComparison> = months
+        .into_iter()
+        .map(|(month, totals)| {
+            let days = totals.days.len();
+            let amber_cost = totals.amber_usage_cost + amber_daily_charge * days as f32;
+            let plan_cost = totals.plan_usage_cost
+                + plan.daily_supply_charge * days as f32
+                + plan.controlled_load_supply_charge * totals.controlled_load_days.len() as f32;
+            MonthComparison {
+                month,
+                days,
+                amber_cost,
+                plan_cost,
+                difference: plan_cost - amber_cost,
+            }
+        })
+        .collect();
+
+    let amber_cost = months.iter().map(|month| month.amber_cost).sum();
+    let plan_cost = months.iter().map(|month| month.plan_cost).sum();
+    Ok(PlanComparison {
+        name: plan.name.clone(),
+        amber_cost,
+        plan_cost,
+        difference: plan_cost - amber_cost,
+        months,
+    })
+}
+
+/// Function to fetch usage for a date range and compare it against every plan in the tariffs file.
+#[tracing::instrument(level = "debug", skip(auth_token, tariffs, billing))]
+pub async fn compare_tariffs(
+    base_url: String,
+    auth_token: SecretString,
+    site_id: String,
+    start_date: String,
+    end_date: String,
+    tariffs: TariffsFile,
+    billing: Option<BillingConfig>,
+) -> Result<Vec<PlanComparison>> {
+    if parse_report_date(&end_date)? < parse_report_date(&start_date)? {
+        bail!(
+            "End date {} is before the start date {}",
+            end_date,
+            start_date
+        );
+    }
+    let usage = get_usage_by_date(base_url, auth_token, site_id, start_date, end_date).await?;
+    tariffs
+        .plans
+        .iter()
+        .map(|plan| compare_plan(&usage, plan, billing.as_ref()))
+        .collect()
+}
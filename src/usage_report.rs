@@ -0,0 +1,228 @@
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::bill::{parse_report_date, FEED_IN_CHANNEL};
+use crate::get_usage_by_date;
+use crate::rest_client::UsageData;
+use crate::schedule::to_nem_time;
+use crate::secret::SecretString;
+
+/// Enum type for how usage intervals are grouped together in a report.
+#[derive(ValueEnum, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum GroupBy {
+    /// Each day, e.g. 2023-12-25.
+    Day,
+    /// Each ISO week, e.g. 2023-W52.
+    Week,
+    /// Each month, e.g. 2023-12.
+    Month,
+    /// Each hour of the day in NEM time, to show when you use the most.
+    HourOfDay,
+    /// Each day of the week, Monday to Sunday.
+    Weekday,
+}
+
+impl GroupBy {
+    /// The group a usage interval belongs to, as a key that sorts in order and a label.
+    pub(crate) fn group(&self, record: &UsageData) -> (String, String) {
+        // Amber's `date` is the NEM date of the interval.
+        let date = record.date.date();
+        match self {
+            GroupBy::Day => (date.to_string(), date.to_string()),
+            GroupBy::Week => {
+                let (year, week, _) = date.to_iso_week_date();
+                let label = format!("{}-W{:02}", year, week);
+                (label.clone(), label)
+            }
+            GroupBy::Month => {
+                let label = format!("{}-{:02}", date.year(), u8::from(date.month()));
+                (label.clone(), label)
+            }
+            GroupBy::HourOfDay => {
+                let label = format!("{:02}:00", to_nem_time(&record.start_time).hour());
+                (label.clone(), label)
+            }
+            GroupBy::Weekday => (
+                date.weekday().number_from_monday().to_string(),
+                date.weekday().to_string(),
+            ),
+        }
+    }
+}
+
+/// Struct type for the usage of a channel in one group.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageGroup {
+    pub group: String,
+    pub channel_type: String,
+    pub intervals: usize,
+    pub kwh: f32,
+    /// Cost in cents, negative for feed in credit.
+    pub cost: f32,
+    /// Cost divided by kWh, what was actually paid (or earned) per kWh.
+    pub average_per_kwh: f32,
+    /// Spot price weighted by the kWh used in each interval.
+    pub average_spot_per_kwh: f32,
+    /// Renewables percentage weighted by the kWh used in each interval.
+    pub renewables: f32,
+}
+
+/// Struct type for running totals while grouping.
+#[derive(Debug, Default)]
+struct GroupTotals {
+    label: String,
+    intervals: usize,
+    kwh: f32,
+    cost: f32,
+    spot_kwh: f32,
+    renewables_kwh: f32,
+}
+
+/// Divide, returning zero when nothing was used.
+fn per_kwh(value: f32, kwh: f32) -> f32 {
+    if kwh.abs() > f32::EPSILON {
+        value / kwh
+    } else {
+        0.0
+    }
+}
+
+/// Roll usage intervals up into groups, one row per group and channel.
+pub fn aggregate_usage(usage: &[UsageData], group_by: GroupBy) -> Vec<UsageGroup> {
+    let mut groups: BTreeMap<(String, String), GroupTotals> = BTreeMap::new();
+    for record in usage {
+        let (sort_key, label) = group_by.group(record);
+        let totals = groups
+            .entry((sort_key, record.channel_type.clone()))
+            .or_insert_with(|| GroupTotals {
+                label,
+                ..Default::default()
+            });
+        totals.intervals += 1;
+        totals.kwh += record.kwh;
+        totals.cost += record.cost;
+        totals.spot_kwh += record.spot_per_kwh * record.kwh;
+        totals.renewables_kwh += record.renewables * record.kwh;
+    }
+
+    groups
+        .into_iter()
+        .map(|((_, channel_type), totals)| UsageGroup {
+            group: totals.label,
+            channel_type,
+            intervals: totals.intervals,
+            kwh: totals.kwh,
+            cost: totals.cost,
+            average_per_kwh: per_kwh(totals.cost, totals.kwh),
+            average_spot_per_kwh: per_kwh(totals.spot_kwh, totals.kwh),
+            renewables: per_kwh(totals.renewables_kwh, totals.kwh),
+        })
+        .collect()
+}
+
+/// Struct type for how green the power used in one group was.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RenewablesShare {
+    pub group: String,
+    /// kWh used from the grid, across the general and controlled load channels.
+    pub kwh: f32,
+    /// kWh used multiplied by the renewables share of the grid at the time.
+    pub renewable_kwh: f32,
+    /// Renewables share of the power used, weighted by the kWh used in each interval.
+    pub renewables: f32,
+    /// Average renewables share of the grid over the same intervals, whatever was used.
+    pub grid_renewables: f32,
+    /// Renewables less grid renewables, above zero when usage was shifted to greener times.
+    pub shift: f32,
+}
+
+/// Struct type for running totals of a renewables group.
+#[derive(Debug, Default)]
+struct RenewablesTotals {
+    label: String,
+    kwh: f32,
+    renewable_kwh: f32,
+    intervals: BTreeMap<String, f32>,
+}
+
+/// Work out the renewables share of the power used from the grid in each group.
+pub fn renewables_share(usage: &[UsageData], group_by: GroupBy) -> Vec<RenewablesShare> {
+    let mut groups: BTreeMap<String, RenewablesTotals> = BTreeMap::new();
+    for record in usage.iter().filter(|r| r.channel_type != FEED_IN_CHANNEL) {
+        let (sort_key, label) = group_by.group(record);
+        let totals = groups.entry(sort_key).or_insert_with(|| RenewablesTotals {
+            label,
+            ..Default::default()
+        });
+        totals.kwh += record.kwh;
+        totals.renewable_kwh += record.kwh * record.renewables / 100.0;
+        // Each channel reports the same grid renewables, count every interval once.
+        totals
+            .intervals
+            .insert(record.start_time.to_string(), record.renewables);
+    }
+
+    groups
+        .into_values()
+        .map(|totals| {
+            let renewables = per_kwh(totals.renewable_kwh * 100.0, totals.kwh);
+            let grid_renewables =
+                totals.intervals.values().sum::<f32>() / totals.intervals.len() as f32;
+            RenewablesShare {
+                group: totals.label,
+                kwh: totals.kwh,
+                renewable_kwh: totals.renewable_kwh,
+                renewables,
+                grid_renewables,
+                shift: renewables - grid_renewables,
+            }
+        })
+        .collect()
+}
+
+/// Function to fetch usage for a date range and roll it up by day, week, month, hour of day or weekday.
+#[tracing::instrument(level = "debug", skip(auth_token))]
+pub async fn get_usage_report(
+    base_url: String,
+    auth_token: SecretString,
+    site_id: String,
+    start_date: String,
+    end_date: String,
+    group_by: GroupBy,
+) -> Result<Vec<UsageGroup>> {
+    if parse_report_date(&end_date)? < parse_report_date(&start_date)? {
+        bail!(
+            "End date {} is before the start date {}",
+            end_date,
+            start_date
+        );
+    }
+    let usage = get_usage_by_date(base_url, auth_token, site_id, start_date, end_date).await?;
+    Ok(aggregate_usage(&usage, group_by))
+}
+
+/// Function to fetch usage for a date range and report the renewables share of the power used.
+#[tracing::instrument(level = "debug", skip(auth_token))]
+pub async fn get_renewables_report(
+    base_url: String,
+    auth_token: SecretString,
+    site_id: String,
+    start_date: String,
+    end_date: String,
+    group_by: GroupBy,
+) -> Result<Vec<RenewablesShare>> {
+    if parse_report_date(&end_date)? < parse_report_date(&start_date)? {
+        bail!(
+            "End date {} is before the start date {}",
+            end_date,
+            start_date
+        );
+    }
+    let usage = get_usage_by_date(base_url, auth_token, site_id, start_date, end_date).await?;
+    Ok(renewables_share(&usage, group_by))
+}
@@ -0,0 +1,229 @@
+use anyhow::Result;
+use iso8601_timestamp::Timestamp;
+use serde::Serialize;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+use crate::bill::FEED_IN_CHANNEL;
+use crate::get_prices;
+use crate::notifier::Notifiers;
+use crate::rest_client::PriceData;
+use crate::secret::SecretString;
+use crate::spike::{find_forecast_spike, is_spiking, SpikeForecast};
+
+/// Length of an Amber price interval in seconds, all queries use the 30min resolution.
+pub const INTERVAL_SECONDS: u64 = 1800;
+
+/// Seconds to wait after an interval boundary so Amber has published the new interval.
+pub const SETTLE_SECONDS: u64 = 30;
+
+/// Interval type Amber uses for the price data of the interval we are in now.
+pub const CURRENT_INTERVAL: &str = "CurrentInterval";
+
+/// Struct type holding the options used to run watch mode.
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    pub forecast_intervals: u32,
+    pub channel_type: String,
+    pub price_limit: Option<f32>,
+    pub once: bool,
+}
+
+/// Enum type to describe the kinds of alerts watch mode can raise.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum AlertKind {
+    CurrentSpike,
+    ForecastSpike,
+    /// Exporting solar costs money, Amber's feed in price is above zero, time to curtail the inverter.
+    FeedInCostsMoney,
+    /// Exporting solar earns money again.
+    FeedInRecovered,
+}
+
+/// Struct type for an alert raised by watch mode.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Alert {
+    pub kind: AlertKind,
+    pub message: String,
+    pub spike: Option<SpikeForecast>,
+}
+
+/// Struct type for the result of a single watch mode poll.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchUpdate {
+    pub prices: Vec<PriceData>,
+    pub alerts: Vec<Alert>,
+}
+
+/// Tracks what has already been alerted on, so the same spike is only reported once.
+#[derive(Debug, Default)]
+pub struct AlertState {
+    current_spike_start: Option<Timestamp>,
+    /// End of the last forecast spike alerted on, runs starting before it are the same spike.
+    forecast_spike_end: Option<Timestamp>,
+    feed_in_costs_money: bool,
+}
+
+/// Work out which alerts to raise for a set of current and forecast price intervals.
+pub fn evaluate_alerts(
+    prices: &[PriceData],
+    options: &WatchOptions,
+    state: &mut AlertState,
+) -> Vec<Alert> {
+    let mut alerts = Vec::new();
+
+    let current_spike = prices.iter().find(|interval| {
+        interval.interval_type == CURRENT_INTERVAL
+            && interval.channel_type == options.channel_type
+            && is_spiking(interval, options.price_limit)
+    });
+    match current_spike {
+        Some(interval) if state.current_spike_start != Some(interval.start_time) => {
+            state.current_spike_start = Some(interval.start_time);
+            alerts.push(Alert {
+                kind: AlertKind::CurrentSpike,
+                message: format!(
+                    "Current interval is spiking ({}) at {:.2} c/kWh on the {} channel",
+                    interval.spike_status, interval.per_kwh, interval.channel_type
+                ),
+                spike: None,
+            });
+        }
+        Some(_) => (),
+        None => state.current_spike_start = None,
+    }
+
+    // Once a spike is current the rest of its run is still in the forecast, starting straight
+    // after it. That run, or one overlapping the last run alerted on, is the same spike, so only
+    // its end is extended. Amber intervals start one second after the previous one ends.
+    let forecast_spike = find_forecast_spike(prices, &options.channel_type, options.price_limit);
+    if let Some(spike) = forecast_spike {
+        let continues_current = current_spike.is_some_and(|interval| {
+            spike.start_time - time::Duration::seconds(1) <= interval.end_time
+        });
+        let alerted = state
+            .forecast_spike_end
+            .is_some_and(|end| spike.start_time < end);
+        if alerted || continues_current {
+            state.forecast_spike_end = state.forecast_spike_end.max(Some(spike.end_time));
+        } else {
+            state.forecast_spike_end = Some(spike.end_time);
+            alerts.push(Alert {
+                kind: AlertKind::ForecastSpike,
+                message: spike.summary(),
+                spike: Some(spike),
+            });
+        }
+    }
+
+    // Amber's feed in price is a cost, so a price above zero means exporting costs money.
+    let current_feed_in = prices.iter().find(|interval| {
+        interval.interval_type == CURRENT_INTERVAL && interval.channel_type == FEED_IN_CHANNEL
+    });
+    match current_feed_in {
+        Some(interval) if interval.per_kwh > 0.0 && !state.feed_in_costs_money => {
+            state.feed_in_costs_money = true;
+            alerts.push(Alert {
+                kind: AlertKind::FeedInCostsMoney,
+                message: format!(
+                    "Feed in price is above zero (Amber's feed in price is a cost), exporting costs {:.2} c/kWh, curtail solar export",
+                    interval.per_kwh
+                ),
+                spike: None,
+            });
+        }
+        Some(interval) if interval.per_kwh <= 0.0 && state.feed_in_costs_money => {
+            state.feed_in_costs_money = false;
+            alerts.push(Alert {
+                kind: AlertKind::FeedInRecovered,
+                message: format!(
+                    "Feed in price is below zero again (Amber's feed in price is a cost), exporting earns {:.2} c/kWh",
+                    -interval.per_kwh
+                ),
+                spike: None,
+            });
+        }
+        _ => (),
+    }
+
+    alerts
+}
+
+/// Current unix time in seconds.
+pub fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .unwrap_or_default()
+}
+
+/// Unix time the next interval starts at, after `now` (unix seconds).
+pub fn next_interval_boundary(now: u64) -> u64 {
+    (now / INTERVAL_SECONDS + 1) * INTERVAL_SECONDS
+}
+
+/// Time to sleep from `now` (unix seconds) until just after the next interval boundary.
+pub fn duration_until_next_interval(now: u64) -> Duration {
+    Duration::from_secs(next_interval_boundary(now) - now + SETTLE_SECONDS)
+}
+
+/// Fetch the current and forecast prices once and work out any alerts.
+#[tracing::instrument(level = "debug", skip(auth_token, state))]
+pub async fn poll_once(
+    base_url: String,
+    auth_token: SecretString,
+    site_id: String,
+    options: &WatchOptions,
+    state: &mut AlertState,
+) -> Result<WatchUpdate> {
+    let window = format!("current?next={}", options.forecast_intervals);
+    let prices = get_prices(base_url, auth_token, site_id, window).await?;
+    let alerts = evaluate_alerts(&prices, options, state);
+
+    Ok(WatchUpdate { prices, alerts })
+}
+
+/// Run watch mode, polling Amber once per interval and reporting alerts as they are raised.
+/// Alerts are printed and handed to the configured notifiers.
+/// Errors talking to the API are logged and retried on the next interval.
+#[tracing::instrument(level = "debug", skip(auth_token, notifiers))]
+pub async fn run_watch(
+    base_url: String,
+    auth_token: SecretString,
+    site_id: String,
+    options: WatchOptions,
+    notifiers: &Notifiers,
+) -> Result<()> {
+    let mut state = AlertState::default();
+
+    loop {
+        match poll_once(
+            base_url.clone(),
+            auth_token.clone(),
+            site_id.clone(),
+            &options,
+            &mut state,
+        )
+        .await
+        {
+            Ok(update) => {
+                info!("Received {} price intervals", update.prices.len());
+                for alert in &update.alerts {
+                    warn!("{}", alert.message);
+                    println!("{}", serde_json::to_string(alert)?);
+                }
+                notifiers.dispatch(&site_id, &update).await;
+            }
+            Err(error) => error!("Failed to poll Amber API: {}", error),
+        }
+
+        if options.once {
+            return Ok(());
+        }
+
+        tokio::time::sleep(duration_until_next_interval(unix_now())).await;
+    }
+}
@@ -0,0 +1,161 @@
+use amber_client::accuracy::{
+    forecast_accuracy, record_forecast, snapshot, ForecastStore, ACTUAL_INTERVAL,
+};
+use amber_client::rest_client::PriceData;
+use std::path::PathBuf;
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Mock data used in the forecast accuracy test cases
+mod mock_data {
+    // Build a general channel price interval starting `index` half hours after 06:00 UTC.
+    pub fn price_interval(
+        interval_type: &str,
+        index: u32,
+        per_kwh: f32,
+        spike_status: &str,
+    ) -> String {
+        let start_minutes = 6 * 60 + index * 30;
+        let end_minutes = start_minutes + 30;
+        format!(
+            r#"{{
+              "type": "{interval_type}",
+              "date": "2023-12-25T00:00:00.000Z",
+              "duration": 30,
+              "startTime": "2023-12-25T{:02}:{:02}:01.000Z",
+              "endTime": "2023-12-25T{:02}:{:02}:00.000Z",
+              "nemTime": "2023-12-25T{:02}:{:02}:00.000Z",
+              "perKwh": {per_kwh},
+              "renewables": 50.0,
+              "spotPerKwh": {spot},
+              "channelType": "general",
+              "spikeStatus": "{spike_status}",
+              "tariffInformation": {{ "period": "peak" }},
+              "descriptor": "neutral"
+            }}"#,
+            start_minutes / 60,
+            start_minutes % 60,
+            end_minutes / 60,
+            end_minutes % 60,
+            end_minutes / 60,
+            end_minutes % 60,
+            spot = per_kwh / 2.0,
+        )
+    }
+
+    pub fn prices(intervals: &[String]) -> Vec<amber_client::rest_client::PriceData> {
+        serde_json::from_str(&format!("[{}]", intervals.join(","))).unwrap()
+    }
+}
+
+fn test_store(name: &str) -> ForecastStore {
+    let dir: PathBuf = std::env::temp_dir().join(format!("amber-cli-accuracy-{}", name));
+    let _ = std::fs::remove_dir_all(&dir);
+    ForecastStore::new(dir)
+}
+
+/// Test lead times are counted in intervals from the current interval
+#[test]
+fn snapshot_lead_times() {
+    let prices: Vec<PriceData> = mock_data::prices(&[
+        mock_data::price_interval(ACTUAL_INTERVAL, 0, 20.0, "none"),
+        mock_data::price_interval("CurrentInterval", 1, 22.0, "none"),
+        mock_data::price_interval("ForecastInterval", 2, 24.0, "none"),
+        mock_data::price_interval("ForecastInterval", 3, 26.0, "none"),
+    ]);
+    let intervals = snapshot(&prices, "site_id");
+
+    let leads: Vec<i64> = intervals.iter().map(|i| i.lead_intervals).collect();
+    assert_eq!(leads, vec![-1, 0, 1, 2]);
+    assert!(intervals
+        .iter()
+        .all(|i| i.recorded_for.to_string() == "2023-12-25T06:30:01.000Z"));
+}
+
+/// Test forecasts are compared with the actual price by lead time
+#[test]
+fn accuracy_by_lead_time() {
+    let store = test_store("report");
+    // At 06:00, forecast 06:30 at 30c and 07:00 at 40c (a potential spike).
+    let first = mock_data::prices(&[
+        mock_data::price_interval("CurrentInterval", 0, 20.0, "none"),
+        mock_data::price_interval("ForecastInterval", 1, 30.0, "none"),
+        mock_data::price_interval("ForecastInterval", 2, 40.0, "potential"),
+    ]);
+    // At 06:30, forecast 07:00 at 36c, recorded twice.
+    let second = mock_data::prices(&[
+        mock_data::price_interval(ACTUAL_INTERVAL, 0, 21.0, "none"),
+        mock_data::price_interval("CurrentInterval", 1, 25.0, "none"),
+        mock_data::price_interval("ForecastInterval", 2, 36.0, "none"),
+    ]);
+    // At 07:00, the actual prices are known.
+    let third = mock_data::prices(&[
+        mock_data::price_interval(ACTUAL_INTERVAL, 1, 24.0, "none"),
+        mock_data::price_interval("CurrentInterval", 2, 50.0, "spike"),
+    ]);
+    let fourth = mock_data::prices(&[
+        mock_data::price_interval(ACTUAL_INTERVAL, 2, 50.0, "spike"),
+        mock_data::price_interval("CurrentInterval", 3, 30.0, "none"),
+    ]);
+    for prices in [&first, &second, &second, &third, &fourth] {
+        store.append(&snapshot(prices, "site_id")).unwrap();
+    }
+    store.append(&snapshot(&first, "another_site")).unwrap();
+
+    let stored = store.load("site_id").unwrap();
+    let accuracy = forecast_accuracy(&stored, "general");
+    assert_eq!(accuracy.len(), 3);
+
+    // The current interval: 06:00 20 vs 21, 06:30 25 vs 24, 07:00 50 vs 50.
+    assert_eq!(accuracy[0].lead_intervals, 0);
+    assert_eq!(accuracy[0].samples, 3);
+    assert_eq!(accuracy[0].mean_absolute_error, 2.0 / 3.0);
+    assert_eq!(accuracy[0].bias, 0.0);
+    assert_eq!(accuracy[0].spike_hit_rate, Some(1.0));
+
+    // 30min ahead: 06:30 30 vs 24, 07:00 36 vs 50, the repeated snapshot is only counted once.
+    assert_eq!(accuracy[1].lead_minutes, 30);
+    assert_eq!(accuracy[1].samples, 2);
+    assert_eq!(accuracy[1].mean_absolute_error, 10.0);
+    assert_eq!(accuracy[1].bias, -4.0);
+    assert_eq!(accuracy[1].spot_bias, -2.0);
+    assert_eq!(accuracy[1].actual_spikes, 1);
+    assert_eq!(accuracy[1].spike_hit_rate, Some(0.0));
+
+    // 60min ahead: the potential spike forecast for 07:00 was right.
+    assert_eq!(accuracy[2].samples, 1);
+    assert_eq!(accuracy[2].spike_hits, 1);
+    assert_eq!(accuracy[2].false_alarms, 0);
+}
+
+/// Test a snapshot asks for the forecast and the interval that just finished
+#[tokio::test]
+async fn record_snapshot() {
+    let store = test_store("record");
+    let mock_server = MockServer::start().await;
+    let body = format!(
+        "[{},{}]",
+        mock_data::price_interval(ACTUAL_INTERVAL, 0, 20.0, "none"),
+        mock_data::price_interval("CurrentInterval", 1, 22.0, "none")
+    );
+    Mock::given(method("GET"))
+        .and(path("/sites/site_id/prices/current"))
+        .and(query_param("next", "12"))
+        .and(query_param("previous", "1"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(body, "application/json"))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let recorded = record_forecast(
+        mock_server.uri(),
+        "token".into(),
+        "site_id".to_string(),
+        12,
+        &store,
+    )
+    .await
+    .unwrap();
+    assert_eq!(recorded, 2);
+    assert_eq!(store.load("site_id").unwrap().len(), 2);
+}
@@ -0,0 +1,222 @@
+use amber_client::app_config::{
+    permissions_warning, store_keyring_psk, ApiToken, AppConfig, KEYRING_SERVICE,
+};
+use amber_client::secret::SecretString;
+use std::path::PathBuf;
+
+/// Mock keyring backend, shared between entries so a stored token can be read back
+mod mock_keyring {
+    use keyring::credential::{Credential, CredentialApi, CredentialBuilderApi};
+    use std::any::Any;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    static STORE: Mutex<Option<HashMap<String, Vec<u8>>>> = Mutex::new(None);
+
+    struct MockCredential {
+        key: String,
+    }
+
+    /// The secret service backend blocks on its own runtime, so fail like it inside one.
+    fn check_off_runtime() -> keyring::Result<()> {
+        match tokio::runtime::Handle::try_current() {
+            Ok(_) => Err(keyring::Error::PlatformFailure(
+                "Cannot start a runtime from within a runtime".into(),
+            )),
+            Err(_) => Ok(()),
+        }
+    }
+
+    impl CredentialApi for MockCredential {
+        fn set_secret(&self, secret: &[u8]) -> keyring::Result<()> {
+            check_off_runtime()?;
+            let mut store = STORE.lock().unwrap();
+            store
+                .get_or_insert_with(HashMap::new)
+                .insert(self.key.clone(), secret.to_vec());
+            Ok(())
+        }
+
+        fn get_secret(&self) -> keyring::Result<Vec<u8>> {
+            check_off_runtime()?;
+            let store = STORE.lock().unwrap();
+            store
+                .as_ref()
+                .and_then(|store| store.get(&self.key).cloned())
+                .ok_or(keyring::Error::NoEntry)
+        }
+
+        fn delete_credential(&self) -> keyring::Result<()> {
+            let mut store = STORE.lock().unwrap();
+            match store.as_mut().and_then(|store| store.remove(&self.key)) {
+                Some(_) => Ok(()),
+                None => Err(keyring::Error::NoEntry),
+            }
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+    }
+
+    struct MockBuilder;
+
+    impl CredentialBuilderApi for MockBuilder {
+        fn build(
+            &self,
+            _target: Option<&str>,
+            service: &str,
+            user: &str,
+        ) -> keyring::Result<Box<Credential>> {
+            Ok(Box::new(MockCredential {
+                key: format!("{}/{}", service, user),
+            }))
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+    }
+
+    pub fn install() {
+        keyring::set_default_credential_builder(Box::new(MockBuilder));
+    }
+
+    pub fn stored(service: &str, user: &str) -> Option<Vec<u8>> {
+        let store = STORE.lock().unwrap();
+        store
+            .as_ref()
+            .and_then(|store| store.get(&format!("{}/{}", service, user)).cloned())
+    }
+}
+
+fn token() -> ApiToken {
+    ApiToken {
+        name: "home".to_string(),
+        psk: SecretString::default(),
+        psk_file: None,
+        psk_command: None,
+        keyring: false,
+    }
+}
+
+fn temp_file(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("amber-cli-token-{}", name));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+/// Test the PSK is read from a file named in the config file, and the newline is trimmed
+#[test]
+fn psk_from_file() {
+    let psk_file = temp_file("secret", "psk_fromfile\n");
+    let config_file = temp_file(
+        "config.toml",
+        &format!(
+            r#"
+[amberconfig]
+base_url = "https://api.amber.com.au/v1"
+
+[userconfig]
+state = "vic"
+
+[apitoken]
+name = "home"
+psk_file = "{}"
+"#,
+            psk_file.display()
+        ),
+    );
+    let config = AppConfig::load(Some(&config_file.display().to_string()), None).unwrap();
+    assert_eq!(config.apitoken.psk.expose_secret(), "psk_fromfile");
+    assert!(config.problems().is_empty());
+}
+
+/// Test the PSK is read from a command, and a failing command is reported
+#[test]
+fn psk_from_command() {
+    let api_token = ApiToken {
+        psk_command: Some("echo psk_fromcommand".to_string()),
+        ..token()
+    };
+    assert_eq!(
+        api_token.read_psk().unwrap().expose_secret(),
+        "psk_fromcommand"
+    );
+
+    let api_token = ApiToken {
+        psk_command: Some("echo locked >&2; exit 3".to_string()),
+        ..token()
+    };
+    let error = api_token.read_psk().unwrap_err().to_string();
+    assert!(error.contains("failed"));
+    assert!(error.contains("locked"));
+}
+
+/// Test exactly one PSK source has to be set
+#[test]
+fn one_psk_source() {
+    assert!(token().read_psk().is_err());
+
+    let api_token = ApiToken {
+        psk: "psk_inline".into(),
+        keyring: true,
+        ..token()
+    };
+    let error = api_token.read_psk().unwrap_err().to_string();
+    assert!(error.contains("Only set one of"));
+}
+
+/// Test a token stored in the keyring can be read back by name
+#[test]
+fn psk_from_keyring() {
+    mock_keyring::install();
+    let api_token = ApiToken {
+        keyring: true,
+        ..token()
+    };
+    let error = api_token.read_psk().unwrap_err().to_string();
+    assert!(error.contains("config store-token"));
+
+    assert!(store_keyring_psk("home", &"not a token".into()).is_err());
+    store_keyring_psk("home", &"psk_fromkeyring".into()).unwrap();
+    assert_eq!(
+        mock_keyring::stored(KEYRING_SERVICE, "home"),
+        Some(b"psk_fromkeyring".to_vec())
+    );
+    assert_eq!(
+        api_token.read_psk().unwrap().expose_secret(),
+        "psk_fromkeyring"
+    );
+}
+
+/// Test the keyring can be used from inside the tokio runtime, as `AppConfig::get` does
+#[tokio::test(flavor = "current_thread")]
+async fn psk_from_keyring_in_runtime() {
+    mock_keyring::install();
+    let api_token = ApiToken {
+        name: "runtime".to_string(),
+        keyring: true,
+        ..token()
+    };
+    store_keyring_psk("runtime", &"psk_fromruntime".into()).unwrap();
+    assert_eq!(
+        api_token.read_psk().unwrap().expose_secret(),
+        "psk_fromruntime"
+    );
+}
+
+/// Test config files other users can read are warned about
+#[cfg(unix)]
+#[test]
+fn readable_config_warning() {
+    use std::os::unix::fs::PermissionsExt;
+    let path = temp_file("permissions.toml", "");
+
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+    let warning = permissions_warning(&path).unwrap();
+    assert!(warning.contains("mode 644"));
+
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+    assert_eq!(permissions_warning(&path), None);
+}
@@ -0,0 +1,72 @@
+use amber_client::app_config::{find_config_file, AppConfig};
+use std::env;
+use std::path::PathBuf;
+
+/// Mock data used in the config environment variable test cases
+mod mock_data {
+    pub fn config() -> String {
+        r#"
+[amberconfig]
+base_url = "https://api.amber.com.au/v1"
+
+[userconfig]
+state = "vic"
+
+[apitoken]
+name = "file token"
+psk = "psk_fromfile"
+"#
+        .to_string()
+    }
+}
+
+/// Test the config file lookup order and AMBER_* environment variable overrides
+#[tokio::test]
+async fn config_from_environment() {
+    // Environment variables are shared by the whole process, so every case runs in one test.
+    let dir: PathBuf = env::temp_dir().join("amber-cli-config-env");
+    let _ = std::fs::remove_dir_all(&dir);
+    let xdg_config = dir.join("amber-cli").join("config.toml");
+
+    // $AMBER_CONFIG is used before the default location, and --config-file before both.
+    env::set_var("AMBER_CONFIG", "/srv/amber.toml");
+    env::set_var("XDG_CONFIG_HOME", &dir);
+    assert_eq!(
+        find_config_file(None),
+        Some(PathBuf::from("/srv/amber.toml"))
+    );
+    assert_eq!(
+        find_config_file(Some(PathBuf::from("cli.toml"))),
+        Some(PathBuf::from("cli.toml"))
+    );
+
+    // The default location is only used when the file exists.
+    env::remove_var("AMBER_CONFIG");
+    assert_eq!(find_config_file(None), None);
+    std::fs::create_dir_all(xdg_config.parent().unwrap()).unwrap();
+    std::fs::write(&xdg_config, mock_data::config()).unwrap();
+    assert_eq!(find_config_file(None), Some(xdg_config.clone()));
+
+    // Environment variables override values in the file.
+    env::set_var("AMBER_APITOKEN__PSK", "psk_fromenv");
+    env::set_var("AMBER_BILLING__DAILY_SUPPLY_CHARGE", "110.5");
+    let config = AppConfig::get(Some(xdg_config.display().to_string()), None)
+        .await
+        .unwrap();
+    assert_eq!(config.apitoken.name, "file token");
+    assert_eq!(config.apitoken.psk.expose_secret(), "psk_fromenv");
+    assert_eq!(config.billing.unwrap().daily_supply_charge, 110.5);
+
+    // Without a file the whole config comes from the environment.
+    env::set_var("AMBER_AMBERCONFIG__BASE_URL", "https://api.amber.com.au/v1");
+    env::set_var("AMBER_USERCONFIG__STATE", "qld");
+    env::set_var("AMBER_APITOKEN__NAME", "container");
+    let config = AppConfig::get(None, None).await.unwrap();
+    assert_eq!(config.userconfig.state, "qld");
+    assert_eq!(config.apitoken.psk.expose_secret(), "psk_fromenv");
+
+    env::remove_var("AMBER_APITOKEN__NAME");
+    let error = AppConfig::get(None, None).await.unwrap_err().to_string();
+    assert!(error.contains("AMBER_* environment variables"));
+    assert!(error.contains("missing field `name`"));
+}
@@ -0,0 +1,74 @@
+use amber_client::app_config::AppConfig;
+use std::path::PathBuf;
+
+/// Mock data used in the config file test cases
+mod mock_data {
+    pub fn config(base_url: &str, state: &str, psk: &str) -> String {
+        format!(
+            r#"
+[amberconfig]
+base_url = "{base_url}"
+
+[userconfig]
+state = "{state}"
+
+[apitoken]
+name = "test token"
+psk = "{psk}"
+"#
+        )
+    }
+}
+
+// Write a config file to a temp dir, returning its path.
+fn write_config(name: &str, contents: &str) -> String {
+    let path: PathBuf = std::env::temp_dir().join(format!("amber-cli-config-{}.toml", name));
+    std::fs::write(&path, contents).unwrap();
+    path.display().to_string()
+}
+
+/// Test a good config file has no problems
+#[tokio::test]
+async fn valid_config() {
+    let path = write_config(
+        "valid",
+        &mock_data::config(
+            "https://api.amber.com.au/v1",
+            "vic",
+            "psk_0123456789abcdef0123456789abcdef",
+        ),
+    );
+    let config = AppConfig::get(Some(path), None).await.unwrap();
+    assert_eq!(config.userconfig.state, "vic");
+}
+
+/// Test every problem in a config file is reported
+#[test]
+fn invalid_values() {
+    let path = write_config(
+        "invalid",
+        &mock_data::config("http://api.amber.com.au/v1", "victoria", "Your PSK"),
+    );
+    let problems = AppConfig::load(Some(&path), None).unwrap().problems();
+
+    assert_eq!(problems.len(), 3);
+    assert!(problems[0].contains("must be an https URL"));
+    assert!(problems[1].contains("\"victoria\" must be one of nsw, vic"));
+    assert!(problems[2].contains("start with \"psk_\""));
+}
+
+/// Test a missing section names the file and the field
+#[tokio::test]
+async fn missing_section() {
+    let path = write_config(
+        "missing",
+        "[amberconfig]\nbase_url = \"https://api.amber.com.au/v1\"\n",
+    );
+    let error = AppConfig::get(Some(path.clone()), None)
+        .await
+        .unwrap_err()
+        .to_string();
+
+    assert!(error.contains(&path));
+    assert!(error.contains("missing field `userconfig`"));
+}
@@ -0,0 +1,118 @@
+use amber_client::battery::{simulate_battery, BatteryOptions, Strategy};
+use amber_client::rest_client::UsageData;
+
+/// Mock data used in the battery test cases
+mod mock_data {
+    // Build a usage interval starting at `start` UTC on 2023-12-25.
+    pub fn usage_interval(
+        start: &str,
+        channel_type: &str,
+        kwh: f32,
+        per_kwh: f32,
+        spike_status: &str,
+    ) -> String {
+        format!(
+            r#"{{
+              "type": "Usage",
+              "duration": 30,
+              "date": "2023-12-25T00:00:00.000Z",
+              "endTime": "2023-12-25T{start}:00.000Z",
+              "quality": "billable",
+              "kwh": {kwh},
+              "nemTime": "2023-12-25T{start}:00.000Z",
+              "perKwh": {per_kwh},
+              "channelType": "{channel_type}",
+              "channelIdentifier": "E1",
+              "cost": {cost},
+              "renewables": 50.0,
+              "spotPerKwh": 10.0,
+              "startTime": "2023-12-25T{start}:01.000Z",
+              "spikeStatus": "{spike_status}",
+              "tariffInformation": {{ "period": "peak" }},
+              "descriptor": "neutral"
+            }}"#,
+            cost = kwh * per_kwh
+        )
+    }
+}
+
+fn usage(intervals: &[String]) -> Vec<UsageData> {
+    serde_json::from_str(&format!("[{}]", intervals.join(","))).unwrap()
+}
+
+fn options(strategy: Strategy, efficiency: f32) -> BatteryOptions {
+    BatteryOptions {
+        capacity_kwh: 10.0,
+        // 2kWh per 30 minute interval.
+        power_kw: 4.0,
+        efficiency,
+        strategy,
+        charge_below: 10.0,
+        discharge_above: 30.0,
+        battery_cost: None,
+    }
+}
+
+/// Test excess solar is stored and used later in the day
+#[test]
+fn self_consumption() {
+    let usage = usage(&[
+        mock_data::usage_interval("02:00", "general", 0.0, 20.0, "none"),
+        mock_data::usage_interval("02:00", "feedIn", 3.0, -5.0, "none"),
+        mock_data::usage_interval("08:00", "general", 2.0, 40.0, "none"),
+        mock_data::usage_interval("08:00", "controlledLoad", 5.0, 15.0, "none"),
+    ]);
+    let simulation = simulate_battery(&usage, &options(Strategy::SelfConsumption, 1.0));
+
+    assert_eq!(simulation.days, 1);
+    assert_eq!(simulation.export_kwh_without_battery, 3.0);
+    assert_eq!(simulation.export_kwh_with_battery, 1.0);
+    assert_eq!(simulation.import_kwh_with_battery, 0.0);
+    assert_eq!(simulation.cost_without_battery, 65.0);
+    assert_eq!(simulation.cost_with_battery, -5.0);
+    assert_eq!(simulation.savings, 70.0);
+    assert_eq!(simulation.charged_kwh, 2.0);
+    assert_eq!(simulation.discharged_kwh, 2.0);
+    assert_eq!(simulation.cycles, 0.2);
+}
+
+/// Test grid charging when cheap, and discharging and exporting when expensive
+#[test]
+fn arbitrage() {
+    let usage = usage(&[
+        mock_data::usage_interval("02:00", "general", 1.0, 8.0, "none"),
+        mock_data::usage_interval("08:00", "general", 1.0, 35.0, "none"),
+        mock_data::usage_interval("08:00", "feedIn", 0.0, -50.0, "none"),
+    ]);
+    let simulation = simulate_battery(&usage, &options(Strategy::Arbitrage, 0.8));
+
+    assert_eq!(simulation.import_kwh_with_battery, 3.0);
+    assert_eq!(simulation.charged_kwh, 2.0);
+    // 2kWh charged at 80% efficiency.
+    assert_eq!(simulation.discharged_kwh, 1.6);
+    assert!((simulation.export_kwh_with_battery - 0.6).abs() < 0.0001);
+    assert_eq!(simulation.cost_without_battery, 43.0);
+    assert!((simulation.cost_with_battery - -6.0).abs() < 0.001);
+}
+
+/// Test the battery is held for spikes, then payback is worked out from the annual savings
+#[test]
+fn spike_export() {
+    let usage = usage(&[
+        mock_data::usage_interval("02:00", "general", 0.0, 5.0, "none"),
+        mock_data::usage_interval("06:00", "general", 1.0, 60.0, "none"),
+        mock_data::usage_interval("08:00", "general", 1.0, 300.0, "spike"),
+        mock_data::usage_interval("08:00", "feedIn", 0.0, -280.0, "spike"),
+    ]);
+    let mut options = options(Strategy::SpikeExport, 1.0);
+    options.battery_cost = Some(10_000.0);
+    let simulation = simulate_battery(&usage, &options);
+
+    // Not used at 60c, all of it used in the spike.
+    assert_eq!(simulation.discharged_kwh, 2.0);
+    assert_eq!(simulation.cost_without_battery, 360.0);
+    assert_eq!(simulation.cost_with_battery, 10.0 + 60.0 - 280.0);
+    assert_eq!(simulation.savings, 570.0);
+    assert_eq!(simulation.annual_savings, 5.7 * 365.0);
+    assert_eq!(simulation.payback_years, Some(10_000.0 / (5.7 * 365.0)));
+}
@@ -0,0 +1,157 @@
+use amber_client::app_config::BillingConfig;
+use amber_client::bill::{build_bill, get_bill, parse_report_date};
+use amber_client::rest_client::UsageData;
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Mock data used in the bill test cases
+mod mock_data {
+    // Build a usage interval, costs are in cents and negative for feed in.
+    pub fn usage_interval(
+        date: &str,
+        hour: u32,
+        channel_type: &str,
+        period: &str,
+        kwh: f32,
+        cost: f32,
+    ) -> String {
+        format!(
+            r#"{{
+              "type": "Usage",
+              "duration": 30,
+              "date": "{date}T00:00:00.000Z",
+              "endTime": "{date}T{hour:02}:30:00.000Z",
+              "quality": "billable",
+              "kwh": {kwh},
+              "nemTime": "{date}T{hour:02}:30:00.000Z",
+              "perKwh": 20.0,
+              "channelType": "{channel_type}",
+              "channelIdentifier": "E1",
+              "cost": {cost},
+              "renewables": 50.0,
+              "spotPerKwh": 10.0,
+              "startTime": "{date}T{hour:02}:00:01.000Z",
+              "spikeStatus": "none",
+              "tariffInformation": {{ "period": "{period}" }},
+              "descriptor": "neutral"
+            }}"#
+        )
+    }
+
+    pub fn usage() -> String {
+        let intervals = [
+            usage_interval("2023-12-01", 7, "general", "peak", 1.5, 45.0),
+            usage_interval("2023-12-01", 2, "general", "offPeak", 2.0, 20.0),
+            usage_interval("2023-12-01", 3, "controlledLoad", "offPeak", 3.0, 24.0),
+            usage_interval("2023-12-01", 12, "feedIn", "shoulder", 4.0, -20.0),
+            usage_interval("2023-12-02", 7, "general", "peak", 1.0, 30.0),
+            usage_interval("2023-12-02", 12, "feedIn", "shoulder", 2.0, -8.0),
+        ];
+        format!("[{}]", intervals.join(","))
+    }
+}
+
+fn billing() -> BillingConfig {
+    BillingConfig {
+        daily_supply_charge: 100.0,
+        monthly_membership_fee: 365.0 / 12.0 * 80.0,
+    }
+}
+
+/// Test usage is totalled into import, feed in and net cost
+#[test]
+fn bill_totals() {
+    let usage: Vec<UsageData> = serde_json::from_str(&mock_data::usage()).unwrap();
+    let start = parse_report_date("2023-12-01").unwrap();
+    let end = parse_report_date("2023-12-02").unwrap();
+    let bill = build_bill(&usage, start, end, Some(&billing()));
+
+    assert_eq!(bill.days, 2);
+    assert_eq!(bill.import_kwh, 7.5);
+    assert_eq!(bill.import_cost, 119.0);
+    assert_eq!(bill.feed_in_kwh, 6.0);
+    assert_eq!(bill.feed_in_credit, 28.0);
+    assert_eq!(bill.supply_charge, 200.0);
+    assert_eq!(bill.membership_fee.round(), 160.0);
+    assert_eq!(bill.net_cost.round(), 119.0 - 28.0 + 200.0 + 160.0);
+}
+
+/// Test the breakdown per channel, tariff period and day
+#[test]
+fn bill_breakdown() {
+    let usage: Vec<UsageData> = serde_json::from_str(&mock_data::usage()).unwrap();
+    let start = parse_report_date("2023-12-01").unwrap();
+    let end = parse_report_date("2023-12-02").unwrap();
+    let bill = build_bill(&usage, start, end, None);
+
+    let channels: Vec<(&str, f32, f32)> = bill
+        .channels
+        .iter()
+        .map(|channel| (channel.channel_type.as_str(), channel.kwh, channel.cost))
+        .collect();
+    assert_eq!(
+        channels,
+        vec![
+            ("controlledLoad", 3.0, 24.0),
+            ("feedIn", 6.0, -28.0),
+            ("general", 4.5, 95.0)
+        ]
+    );
+
+    let general_peak = bill
+        .periods
+        .iter()
+        .find(|period| period.channel_type == "general" && period.period == "peak")
+        .unwrap();
+    assert_eq!(general_peak.kwh, 2.5);
+    assert_eq!(general_peak.cost, 75.0);
+    assert_eq!(bill.periods.len(), 4);
+
+    assert_eq!(bill.daily.len(), 2);
+    assert_eq!(bill.daily[0].date, "2023-12-01");
+    assert_eq!(bill.daily[0].import_cost, 89.0);
+    assert_eq!(bill.daily[0].net_cost, 69.0);
+    assert_eq!(bill.daily[1].feed_in_credit, 8.0);
+    // No fixed charges without a [billing] section.
+    assert_eq!(bill.net_cost, 91.0);
+}
+
+/// Test the bill is built from the usage endpoint, and bad dates are an error
+#[tokio::test]
+async fn bill_from_api() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/sites/site_id/usage"))
+        .and(query_param("startDate", "2023-12-01"))
+        .and(query_param("endDate", "2023-12-02"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_raw(mock_data::usage(), "application/json"),
+        )
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let bill = get_bill(
+        mock_server.uri(),
+        "token".into(),
+        "site_id".to_string(),
+        "2023-12-01".to_string(),
+        "2023-12-02".to_string(),
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(bill.import_cost, 119.0);
+
+    assert!(get_bill(
+        mock_server.uri(),
+        "token".into(),
+        "site_id".to_string(),
+        "2023-12-02".to_string(),
+        "2023-12-01".to_string(),
+        None,
+    )
+    .await
+    .is_err());
+    assert!(parse_report_date("01/12/2023").is_err());
+}
@@ -0,0 +1,86 @@
+use amber_client::cache::{self, CachePolicy, ResponseCache};
+use amber_client::rest_client::RestClient;
+
+use std::path::PathBuf;
+use wiremock::matchers::method;
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Create an empty cache directory for a test case
+fn test_cache_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("amber-cli-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+/// Test a stored response is returned for the same URL and token only
+#[test]
+fn cache_is_keyed_by_url_and_token() {
+    let cache = ResponseCache::new(test_cache_dir("keyed"));
+    cache
+        .put("http://amber/sites", "token", "[]", CachePolicy::Forever)
+        .unwrap();
+
+    assert_eq!(
+        cache.get("http://amber/sites", "token"),
+        Some("[]".to_string())
+    );
+    assert_eq!(cache.get("http://amber/sites", "other_token"), None);
+    assert_eq!(cache.get("http://amber/usage", "token"), None);
+}
+
+/// Test expired responses are not returned
+#[test]
+fn expired_responses_are_ignored() {
+    let cache = ResponseCache::new(test_cache_dir("expired"));
+    cache
+        .put("http://amber/sites", "token", "[]", CachePolicy::Seconds(0))
+        .unwrap();
+
+    assert_eq!(cache.get("http://amber/sites", "token"), None);
+}
+
+/// Test interval data expires at the next interval boundary
+#[test]
+fn interval_policy_expires_at_next_interval() {
+    // 2023-12-25T06:10:00Z expires at 06:30:00Z
+    assert_eq!(
+        CachePolicy::UntilNextInterval.expires_at(1_703_484_600),
+        Some(1_703_485_800)
+    );
+    assert_eq!(CachePolicy::Forever.expires_at(1_703_484_600), None);
+}
+
+/// Test clearing the cache removes every stored response
+#[test]
+fn clear_removes_responses() {
+    let cache = ResponseCache::new(test_cache_dir("clear"));
+    assert_eq!(cache.clear().unwrap(), 0);
+
+    cache
+        .put("http://amber/a", "token", "[]", CachePolicy::Forever)
+        .unwrap();
+    cache
+        .put("http://amber/b", "token", "[]", CachePolicy::Forever)
+        .unwrap();
+
+    assert_eq!(cache.clear().unwrap(), 2);
+    assert_eq!(cache.get("http://amber/a", "token"), None);
+}
+
+/// Test the RestClient only queries Amber once when caching is enabled
+#[tokio::test]
+async fn rest_client_uses_cache_when_enabled() {
+    cache::enable(ResponseCache::new(test_cache_dir("client")));
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw("[]", "application/json"))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    for _ in 0..2 {
+        let mut client = RestClient::new_client(mock_server.uri(), "token".into());
+        assert!(client.get_site_data().await.unwrap().is_empty());
+    }
+}
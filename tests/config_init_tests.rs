@@ -0,0 +1,115 @@
+use amber_client::app_config::AppConfig;
+use amber_client::config_init::{
+    render_config, run_config_init, state_for_network, write_config_file,
+};
+use std::io::Cursor;
+use std::path::PathBuf;
+use wiremock::matchers::{header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Mock data used in the config init test cases
+mod mock_data {
+    fn site(id: &str, network: &str) -> String {
+        format!(
+            r#"{{
+              "activeFrom": "2023-08-31T00:00:00.000Z",
+              "channels": [{{ "identifier": "E1", "tariff": "A123", "type": "general" }}],
+              "id": "{id}",
+              "network": "{network}",
+              "nmi": "1234567890",
+              "status": "active"
+            }}"#
+        )
+    }
+
+    // An account with a holiday house in Tasmania and a home on an unknown network.
+    pub fn sites() -> String {
+        format!(
+            "[{},{}]",
+            site("holiday_site", "TasNetworks"),
+            site("home_site", "test_network")
+        )
+    }
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("amber-cli-init-{}", name));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir.join("amber-cli").join("config.toml")
+}
+
+/// Test the state is worked out from the distribution network
+#[test]
+fn network_states() {
+    assert_eq!(state_for_network("Jemena"), Some("vic"));
+    assert_eq!(state_for_network("SA Power Networks"), Some("sa"));
+    assert_eq!(state_for_network("Energex"), Some("qld"));
+    assert_eq!(state_for_network("test_network"), None);
+}
+
+/// Test the wizard checks the token, lets a site be picked and asks for the state when unknown
+#[tokio::test]
+async fn wizard_answers() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/sites"))
+        .and(header("AUTHORIZATION", "Bearer psk_abc123"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_raw(mock_data::sites(), "application/json"),
+        )
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    // Default token name, a bad token, a good one, site 3 then 2, a bad state then a good one.
+    let mut input = Cursor::new("\nnot a token\npsk_abc123\n3\n2\nvictoria\nVIC\n");
+    let mut output = Vec::new();
+    let answers = run_config_init(mock_server.uri(), &mut input, &mut output, false)
+        .await
+        .unwrap();
+
+    assert_eq!(answers.token_name, "amber-cli");
+    assert_eq!(answers.psk.expose_secret(), "psk_abc123");
+    assert_eq!(answers.site_id, "home_site");
+    assert_eq!(answers.state, "vic");
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("start with \"psk_\""));
+    assert!(output.contains("2) home_site (NMI 1234567890, test_network, active)"));
+    assert!(output.contains("Enter a number from 1 to 2"));
+    assert!(output.contains("Enter one of nsw, vic, qld, sa, tas, act, nt, wa"));
+}
+
+/// Test the config file written can be read back, is private and is not overwritten
+#[tokio::test]
+async fn write_private_config() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/sites"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_raw(mock_data::sites(), "application/json"),
+        )
+        .mount(&mock_server)
+        .await;
+    let mut input = Cursor::new("home \"token\"\npsk_abc123\n1\n");
+    let answers = run_config_init(mock_server.uri(), &mut input, &mut Vec::new(), false)
+        .await
+        .unwrap();
+    assert_eq!(answers.state, "tas");
+
+    let path = temp_path("write");
+    let contents = render_config(&answers).unwrap();
+    write_config_file(&path, &contents, false).unwrap();
+    assert!(write_config_file(&path, &contents, false).is_err());
+    write_config_file(&path, &contents, true).unwrap();
+
+    let config = AppConfig::load(Some(&path.display().to_string()), None).unwrap();
+    assert_eq!(config.apitoken.name, "home \"token\"");
+    assert_eq!(config.userconfig.site_id.as_deref(), Some("holiday_site"));
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
@@ -0,0 +1,100 @@
+use amber_client::emissions::{build_emissions_report, state_intensity};
+use amber_client::rest_client::UsageData;
+use amber_client::usage_report::GroupBy;
+
+/// Mock data used in the emissions test cases
+mod mock_data {
+    // Build a usage interval starting at `start` UTC on `date`.
+    fn usage_interval(
+        date: &str,
+        start: &str,
+        channel_type: &str,
+        kwh: f32,
+        renewables: f32,
+    ) -> String {
+        format!(
+            r#"{{
+              "type": "Usage",
+              "duration": 30,
+              "date": "{date}T00:00:00.000Z",
+              "endTime": "{start}:00.000Z",
+              "quality": "billable",
+              "kwh": {kwh},
+              "nemTime": "{start}:00.000Z",
+              "perKwh": 20.0,
+              "channelType": "{channel_type}",
+              "channelIdentifier": "E1",
+              "cost": 0.0,
+              "renewables": {renewables},
+              "spotPerKwh": 10.0,
+              "startTime": "{start}:01.000Z",
+              "spikeStatus": "none",
+              "tariffInformation": {{ "period": "peak" }},
+              "descriptor": "neutral"
+            }}"#
+        )
+    }
+
+    // Two days in December and one in January, with solar exported on the 29th.
+    // The grid averages 50% renewables over the four intervals.
+    pub fn usage() -> String {
+        let intervals = [
+            usage_interval("2023-12-29", "2023-12-28T21:00", "general", 2.0, 20.0),
+            usage_interval(
+                "2023-12-29",
+                "2023-12-28T21:00",
+                "controlledLoad",
+                1.0,
+                20.0,
+            ),
+            usage_interval("2023-12-29", "2023-12-29T02:00", "feedIn", 4.0, 30.0),
+            usage_interval("2023-12-30", "2023-12-29T21:00", "general", 2.0, 50.0),
+            usage_interval("2024-01-01", "2023-12-31T21:00", "general", 1.0, 100.0),
+        ];
+        format!("[{}]", intervals.join(","))
+    }
+}
+
+fn usage() -> Vec<UsageData> {
+    serde_json::from_str(&mock_data::usage()).unwrap()
+}
+
+/// Test emissions scale with the non renewable share against the average, and exports avoid emissions
+#[test]
+fn emissions_by_day() {
+    let report = build_emissions_report(&usage(), GroupBy::Day, 1.0);
+
+    assert_eq!(report.len(), 3);
+    assert_eq!(report[0].group, "2023-12-29");
+    assert_eq!(report[0].import_kwh, 3.0);
+    // 3kWh at 80% non renewable, against an average of 50%
+    assert!((report[0].emissions_kg - 4.8).abs() < 1e-4);
+    assert!((report[0].emissions_per_kwh - 1.6).abs() < 1e-4);
+    // 4kWh exported at 70% non renewable
+    assert_eq!(report[0].export_kwh, 4.0);
+    assert!((report[0].avoided_kg - 5.6).abs() < 1e-4);
+    assert!((report[0].net_kg + 0.8).abs() < 1e-4);
+
+    // Power used at the average renewables percentage is charged the average intensity.
+    assert!((report[1].emissions_per_kwh - 1.0).abs() < 1e-4);
+    assert_eq!(report[2].emissions_kg, 0.0);
+}
+
+/// Test monthly totals use the intensity given
+#[test]
+fn emissions_by_month() {
+    let report = build_emissions_report(&usage(), GroupBy::Month, 0.5);
+    let months: Vec<(&str, f32)> = report
+        .iter()
+        .map(|row| (row.group.as_str(), row.emissions_kg))
+        .collect();
+    assert_eq!(months, vec![("2023-12", 3.4), ("2024-01", 0.0)]);
+}
+
+/// Test the state lookup ignores case and rejects unknown states
+#[test]
+fn state_intensities() {
+    assert_eq!(state_intensity("VIC").unwrap(), 0.79);
+    assert_eq!(state_intensity("wa").unwrap(), 0.51);
+    assert!(state_intensity("swis").is_err());
+}
@@ -0,0 +1,76 @@
+use amber_client::home_assistant::discovery_messages;
+use amber_client::rest_client::{SiteChannels, SiteDetails};
+
+use iso8601_timestamp::Timestamp;
+use serde_json::Value;
+
+/// Mock data used in the Home Assistant test cases
+mod mock_data {
+    use super::*;
+
+    // Site with a general and a feed in channel.
+    pub fn site_with_solar() -> SiteDetails {
+        SiteDetails {
+            active_from: Timestamp::parse("2023-08-31T00:00:00.000Z").unwrap(),
+            channels: vec![
+                SiteChannels {
+                    identifier: "E1".to_string(),
+                    tariff: "A123".to_string(),
+                    tariff_type: "general".to_string(),
+                },
+                SiteChannels {
+                    identifier: "B1".to_string(),
+                    tariff: "A123".to_string(),
+                    tariff_type: "feedIn".to_string(),
+                },
+            ],
+            id: "test_site_id".to_string(),
+            network: "test_network".to_string(),
+            nmi: "1234567890".to_string(),
+            status: "active".to_string(),
+        }
+    }
+}
+
+/// Test an entity is announced for each channel price and the site wide sensors
+#[test]
+fn discovery_topics_are_keyed_by_nmi() {
+    let messages = discovery_messages("homeassistant", "amber", &mock_data::site_with_solar());
+    let topics: Vec<&str> = messages.iter().map(|m| m.topic.as_str()).collect();
+
+    assert_eq!(
+        topics,
+        vec![
+            "homeassistant/sensor/amber_1234567890/general_price/config",
+            "homeassistant/sensor/amber_1234567890/feed_in_price/config",
+            "homeassistant/sensor/amber_1234567890/spot_price/config",
+            "homeassistant/sensor/amber_1234567890/renewables/config",
+            "homeassistant/sensor/amber_1234567890/descriptor/config",
+            "homeassistant/binary_sensor/amber_1234567890/spike/config",
+        ]
+    );
+}
+
+/// Test the price sensor reads the watch mode topics, with units, forecast attributes and device
+#[test]
+fn discovery_price_sensor_config() {
+    let messages = discovery_messages("homeassistant", "amber", &mock_data::site_with_solar());
+    let config: Value = serde_json::from_str(&messages[1].payload).unwrap();
+
+    assert_eq!(config["unique_id"], "amber_1234567890_feed_in_price");
+    assert_eq!(config["state_topic"], "amber/test_site_id/price/feedIn");
+    assert_eq!(
+        config["json_attributes_topic"],
+        "amber/test_site_id/forecast/feedIn"
+    );
+    assert_eq!(config["unit_of_measurement"], "c/kWh");
+    assert_eq!(config["availability_topic"], "amber/status");
+    assert_eq!(config["device"]["identifiers"][0], "amber_1234567890");
+
+    let renewables: Value = serde_json::from_str(&messages[3].payload).unwrap();
+    assert_eq!(renewables["unit_of_measurement"], "%");
+
+    let descriptor: Value = serde_json::from_str(&messages[4].payload).unwrap();
+    assert_eq!(descriptor["device_class"], "enum");
+    assert!(descriptor.get("unit_of_measurement").is_none());
+}
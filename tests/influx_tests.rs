@@ -0,0 +1,111 @@
+use amber_client::app_config::InfluxConfig;
+use amber_client::influx::{escape_tag, to_line_protocol, InfluxWriter};
+use amber_client::rest_client::{PriceData, UsageData};
+
+use wiremock::matchers::{body_string, header, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Mock data used in the InfluxDB test cases
+mod mock_data {
+    // Raw JSON for a single current price interval.
+    pub fn price_json() -> String {
+        r#"[{
+            "type": "CurrentInterval",
+            "date": "2023-12-25T00:00:00.000Z",
+            "duration": 30,
+            "startTime": "2023-12-24T22:30:01.000Z",
+            "endTime": "2023-12-24T23:00:00.000Z",
+            "nemTime": "2023-12-24T23:00:00.000Z",
+            "perKwh": 5.5,
+            "renewables": 73.5,
+            "spotPerKwh": -4.5,
+            "channelType": "general",
+            "spikeStatus": "none",
+            "tariffInformation": { "period": "offPeak" },
+            "descriptor": "extremelyLow",
+            "estimate": true
+        }]"#
+        .to_string()
+    }
+
+    // Raw JSON for a single usage interval.
+    pub fn usage_json() -> String {
+        r#"[{
+            "type": "Usage",
+            "duration": 30,
+            "date": "2023-12-20",
+            "endTime": "2023-12-20T00:00:00.000Z",
+            "quality": "billable",
+            "kwh": 0.25,
+            "nemTime": "2023-12-20T00:00:00.000Z",
+            "perKwh": 20.0,
+            "channelType": "general",
+            "channelIdentifier": "E1",
+            "cost": 5.0,
+            "renewables": 40.0,
+            "spotPerKwh": 8.0,
+            "startTime": "2023-12-19T23:30:01.000Z",
+            "spikeStatus": "none",
+            "tariffInformation": { "period": "peak" },
+            "descriptor": "neutral"
+        }]"#
+        .to_string()
+    }
+}
+
+/// Test price data is written with tags, typed fields and a nanosecond timestamp
+#[test]
+fn price_data_as_line_protocol() {
+    let prices: Vec<PriceData> = serde_json::from_str(&mock_data::price_json()).unwrap();
+
+    assert_eq!(
+        to_line_protocol(&prices, "test_site_id"),
+        "amber_price,site=test_site_id,channel=general,type=CurrentInterval,descriptor=extremelyLow,tariff_period=offPeak per_kwh=5.5,spot_per_kwh=-4.5,renewables=73.5,duration=30i,spike_status=\"none\",estimate=true 1703457001000000000"
+    );
+}
+
+/// Test usage data is written with the usage measurement
+#[test]
+fn usage_data_as_line_protocol() {
+    let usage: Vec<UsageData> = serde_json::from_str(&mock_data::usage_json()).unwrap();
+
+    assert_eq!(
+        to_line_protocol(&usage, "test_site_id"),
+        "amber_usage,site=test_site_id,channel=general,channel_identifier=E1,type=Usage,descriptor=neutral,tariff_period=peak kwh=0.25,cost=5,per_kwh=20,spot_per_kwh=8,renewables=40,duration=30i,quality=\"billable\",spike_status=\"none\" 1703028601000000000"
+    );
+}
+
+/// Test tag values with special characters are escaped
+#[test]
+fn tag_values_are_escaped() {
+    assert_eq!(escape_tag("a b,c=d"), "a\\ b\\,c\\=d");
+}
+
+/// Test data is POSTed to the InfluxDB v2 write endpoint with the token
+#[tokio::test]
+async fn write_to_influxdb_v2() {
+    let mock_server = MockServer::start().await;
+    let prices: Vec<PriceData> = serde_json::from_str(&mock_data::price_json()).unwrap();
+    let writer = InfluxWriter::new(InfluxConfig {
+        url: mock_server.uri(),
+        org: "home".to_string(),
+        bucket: "amber".to_string(),
+        token: "influx_token".into(),
+        timeout_seconds: 5,
+    })
+    .unwrap();
+
+    Mock::given(method("POST"))
+        .and(path("/api/v2/write"))
+        .and(query_param("org", "home"))
+        .and(query_param("bucket", "amber"))
+        .and(query_param("precision", "ns"))
+        .and(header("Authorization", "Token influx_token"))
+        .and(body_string(to_line_protocol(&prices, "test_site_id")))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    writer.write(&prices, "test_site_id").await.unwrap();
+}
@@ -0,0 +1,132 @@
+use amber_client::metrics::{refresh_metrics, render_metrics, MetricsSnapshot};
+use amber_client::rest_client::PriceData;
+
+use tokio::sync::RwLock;
+use wiremock::matchers::method;
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Mock data used in the metrics test cases
+mod mock_data {
+    // Build a single general channel price interval as Amber would return it.
+    fn price_interval(interval_type: &str, start_time: &str, per_kwh: f32, spike: &str) -> String {
+        format!(
+            r#"{{
+              "type": "{interval_type}",
+              "date": "2023-12-25T00:00:00.000Z",
+              "duration": 30,
+              "startTime": "{start_time}",
+              "endTime": "{start_time}",
+              "nemTime": "{start_time}",
+              "perKwh": {per_kwh},
+              "renewables": 55.5,
+              "spotPerKwh": 12.25,
+              "channelType": "general",
+              "spikeStatus": "{spike}",
+              "tariffInformation": {{ "period": "peak" }},
+              "descriptor": "high"
+            }}"#
+        )
+    }
+
+    pub fn prices_json() -> String {
+        let intervals = [
+            price_interval(
+                "CurrentInterval",
+                "2023-12-25T06:00:01.000Z",
+                30.5,
+                "potential",
+            ),
+            price_interval("ForecastInterval", "2023-12-25T07:00:01.000Z", 42.0, "none"),
+            price_interval("ForecastInterval", "2023-12-25T06:30:01.000Z", 35.0, "none"),
+            price_interval("ForecastInterval", "2023-12-25T07:30:01.000Z", 20.0, "none"),
+        ];
+        format!("[{}]", intervals.join(","))
+    }
+}
+
+/// Test the gauges are rendered in the Prometheus text format
+#[test]
+fn render_price_gauges() {
+    let prices: Vec<PriceData> = serde_json::from_str(&mock_data::prices_json()).unwrap();
+    let snapshot = MetricsSnapshot {
+        prices,
+        last_success: Some(1_703_484_000),
+        api_errors: 2,
+    };
+
+    // 2023-12-25T06:00:00Z
+    let metrics = render_metrics("test_site_id", &snapshot, 1_703_484_000);
+
+    for expected in [
+        "# TYPE amber_price_per_kwh gauge",
+        r#"amber_price_per_kwh{site_id="test_site_id",channel="general",interval="current"} 30.5"#,
+        r#"amber_price_per_kwh{site_id="test_site_id",channel="general",interval="next"} 35"#,
+        r#"amber_spot_price_per_kwh{site_id="test_site_id"} 12.25"#,
+        r#"amber_renewables_percent{site_id="test_site_id"} 55.5"#,
+        r#"amber_spike_status{site_id="test_site_id",channel="general"} 1"#,
+        r#"amber_forecast_price_min_per_kwh{site_id="test_site_id",channel="general"} 20"#,
+        r#"amber_forecast_price_max_per_kwh{site_id="test_site_id",channel="general"} 42"#,
+        r#"amber_last_success_timestamp_seconds{site_id="test_site_id"} 1703484000"#,
+        "# TYPE amber_api_errors_total counter",
+        r#"amber_api_errors_total{site_id="test_site_id"} 2"#,
+    ] {
+        assert!(metrics.lines().any(|line| line == expected), "{}", expected);
+    }
+}
+
+/// Test intervals that have ended are no longer exported as the current price
+#[test]
+fn render_skips_ended_intervals() {
+    let prices: Vec<PriceData> = serde_json::from_str(&mock_data::prices_json()).unwrap();
+    let snapshot = MetricsSnapshot {
+        prices,
+        last_success: Some(1_703_484_000),
+        api_errors: 2,
+    };
+
+    // 2023-12-25T07:00:00Z, after the current interval and the first forecast have ended.
+    let metrics = render_metrics("test_site_id", &snapshot, 1_703_487_600);
+
+    assert!(!metrics.contains("amber_price_per_kwh{"));
+    assert!(!metrics.contains("amber_spike_status{"));
+    assert!(!metrics.contains("amber_renewables_percent{"));
+    assert!(metrics
+        .lines()
+        .any(|line| line == r#"amber_api_errors_total{site_id="test_site_id"} 2"#));
+}
+
+/// Test a failed update counts an error and keeps the last good data
+#[tokio::test]
+async fn refresh_counts_api_errors() {
+    let mock_server = MockServer::start().await;
+    let snapshot = RwLock::new(MetricsSnapshot::default());
+
+    Mock::given(method("GET"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_raw(mock_data::prices_json(), "application/json"),
+        )
+        .up_to_n_times(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&mock_server)
+        .await;
+
+    for expected in [true, false] {
+        let refreshed = refresh_metrics(
+            mock_server.uri(),
+            "token".into(),
+            "test_site_id".to_string(),
+            3,
+            &snapshot,
+        )
+        .await;
+        assert_eq!(refreshed, expected);
+    }
+
+    let snapshot = snapshot.read().await;
+    assert_eq!(snapshot.prices.len(), 4);
+    assert!(snapshot.last_success.is_some());
+    assert_eq!(snapshot.api_errors, 1);
+}
@@ -0,0 +1,208 @@
+use amber_client::app_config::MqttConfig;
+use amber_client::mqtt::{status_topic, update_messages, MqttMessage, MqttPublisher, ONLINE};
+use amber_client::rest_client::PriceData;
+use amber_client::watch::WatchUpdate;
+
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::timeout;
+
+/// Mock data used in the MQTT test cases
+mod mock_data {
+    // Raw JSON for the current general and feedIn intervals plus one forecast interval.
+    pub fn current_prices_json() -> String {
+        r#"[
+          {
+            "type": "CurrentInterval",
+            "date": "2023-12-25T00:00:00.000Z",
+            "duration": 30,
+            "startTime": "2023-12-24T22:30:01.000Z",
+            "endTime": "2023-12-24T23:00:00.000Z",
+            "nemTime": "2023-12-24T23:00:00.000Z",
+            "perKwh": 5.5,
+            "renewables": 73.5,
+            "spotPerKwh": -4.5,
+            "channelType": "general",
+            "spikeStatus": "none",
+            "tariffInformation": { "period": "offPeak" },
+            "descriptor": "extremelyLow",
+            "estimate": true
+          },
+          {
+            "type": "CurrentInterval",
+            "date": "2023-12-25T00:00:00.000Z",
+            "duration": 30,
+            "startTime": "2023-12-24T22:30:01.000Z",
+            "endTime": "2023-12-24T23:00:00.000Z",
+            "nemTime": "2023-12-24T23:00:00.000Z",
+            "perKwh": 4.5,
+            "renewables": 73.5,
+            "spotPerKwh": -4.5,
+            "channelType": "feedIn",
+            "spikeStatus": "none",
+            "tariffInformation": { "period": "offPeak" },
+            "descriptor": "extremelyLow",
+            "estimate": true
+          },
+          {
+            "type": "ForecastInterval",
+            "date": "2023-12-25T00:00:00.000Z",
+            "duration": 30,
+            "startTime": "2023-12-24T23:00:01.000Z",
+            "endTime": "2023-12-24T23:30:00.000Z",
+            "nemTime": "2023-12-24T23:30:00.000Z",
+            "perKwh": 6.5,
+            "renewables": 70.0,
+            "spotPerKwh": -2.0,
+            "channelType": "general",
+            "spikeStatus": "none",
+            "tariffInformation": { "period": "offPeak" },
+            "descriptor": "veryLow"
+          }
+        ]"#
+        .to_string()
+    }
+}
+
+/// Test each current channel price, renewables and spike status get their own topic
+#[test]
+fn mqtt_messages_for_watch_update() {
+    let prices: Vec<PriceData> = serde_json::from_str(&mock_data::current_prices_json()).unwrap();
+    let update = WatchUpdate {
+        prices,
+        alerts: Vec::new(),
+    };
+
+    let messages = update_messages("amber", "test_site_id", &update);
+    let topics: Vec<&str> = messages.iter().map(|m| m.topic.as_str()).collect();
+
+    assert_eq!(
+        topics,
+        vec![
+            "amber/test_site_id/price/general",
+            "amber/test_site_id/forecast/general",
+            "amber/test_site_id/price/feedIn",
+            "amber/test_site_id/renewables",
+            "amber/test_site_id/spike",
+        ]
+    );
+    assert!(messages[0].payload.contains(r#""perKwh":5.5"#));
+    assert!(messages[1]
+        .payload
+        .starts_with(r#"{"forecasts":[{"type":"ForecastInterval""#));
+    assert_eq!(
+        messages[3],
+        MqttMessage {
+            topic: "amber/test_site_id/renewables".to_string(),
+            payload: "73.5".to_string(),
+        }
+    );
+    assert_eq!(messages[4].payload, "none");
+}
+
+/// Test nothing is published when the update has no current interval
+#[test]
+fn mqtt_messages_empty_without_current_interval() {
+    let update = WatchUpdate {
+        prices: Vec::new(),
+        alerts: Vec::new(),
+    };
+
+    assert!(update_messages("amber", "test_site_id", &update).is_empty());
+    assert_eq!(status_topic("amber"), "amber/status");
+}
+
+/// Read one MQTT packet from a client, returning its type and body.
+async fn read_packet(stream: &mut TcpStream) -> (u8, Vec<u8>) {
+    let packet_type = stream.read_u8().await.unwrap() >> 4;
+    let mut length = 0usize;
+    for shift in (0..28).step_by(7) {
+        let byte = stream.read_u8().await.unwrap();
+        length |= usize::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+    let mut body = vec![0; length];
+    stream.read_exact(&mut body).await.unwrap();
+    (packet_type, body)
+}
+
+/// Accept a connection as the broker would, and return the first message published on it.
+async fn accept_and_read_publish(listener: &TcpListener) -> (TcpStream, MqttMessage) {
+    let (mut stream, _) = listener.accept().await.unwrap();
+    assert_eq!(read_packet(&mut stream).await.0, 1, "expected CONNECT");
+    // CONNACK, session not present, connection accepted.
+    stream.write_all(&[0x20, 0x02, 0x00, 0x00]).await.unwrap();
+
+    let (packet_type, body) = read_packet(&mut stream).await;
+    assert_eq!(packet_type, 3, "expected PUBLISH");
+    let topic_length = usize::from(u16::from_be_bytes([body[0], body[1]]));
+    let message = MqttMessage {
+        topic: String::from_utf8(body[2..2 + topic_length].to_vec()).unwrap(),
+        payload: String::from_utf8(body[2 + topic_length..].to_vec()).unwrap(),
+    };
+    (stream, message)
+}
+
+/// Test the online status is published again after the broker drops the connection
+#[tokio::test]
+async fn mqtt_online_status_is_republished_on_reconnect() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let config: MqttConfig = serde_json::from_value(serde_json::json!({
+        "host": "127.0.0.1",
+        "port": listener.local_addr().unwrap().port(),
+        "qos": 0,
+    }))
+    .unwrap();
+    let online = MqttMessage {
+        topic: status_topic("amber"),
+        payload: ONLINE.to_string(),
+    };
+
+    let _publisher = MqttPublisher::connect(config).await.unwrap();
+    let (first, message) = timeout(Duration::from_secs(15), accept_and_read_publish(&listener))
+        .await
+        .unwrap();
+    assert_eq!(message, online);
+
+    // The broker restarts, the client reconnects and has to say it is online again.
+    drop(first);
+    let (_second, message) = timeout(Duration::from_secs(15), accept_and_read_publish(&listener))
+        .await
+        .expect("online was not published after reconnecting");
+    assert_eq!(message, online);
+}
+
+/// Test a username without a password is still sent to the broker, and a password alone is refused
+#[tokio::test]
+async fn mqtt_username_without_password() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let config: MqttConfig = serde_json::from_value(serde_json::json!({
+        "host": "127.0.0.1",
+        "port": port,
+        "username": "amber_user",
+    }))
+    .unwrap();
+
+    let _publisher = MqttPublisher::connect(config).await.unwrap();
+    let (mut stream, _) = timeout(Duration::from_secs(15), listener.accept())
+        .await
+        .unwrap()
+        .unwrap();
+    let (packet_type, body) = read_packet(&mut stream).await;
+    assert_eq!(packet_type, 1, "expected CONNECT");
+    // The connect flags follow the protocol name and level, the username flag is set.
+    assert_eq!(body[7] & 0x80, 0x80);
+    assert!(body.windows(10).any(|bytes| bytes == b"amber_user"));
+
+    let config: MqttConfig = serde_json::from_value(serde_json::json!({
+        "host": "127.0.0.1",
+        "port": port,
+        "password": "secret",
+    }))
+    .unwrap();
+    assert!(MqttPublisher::connect(config).await.is_err());
+}
@@ -0,0 +1,112 @@
+use amber_client::app_config::WebhookConfig;
+use amber_client::notifier::{
+    NotificationEvent, NotificationPayload, WebhookNotifier, SIGNATURE_HEADER,
+};
+
+use iso8601_timestamp::Timestamp;
+use std::collections::HashMap;
+use wiremock::matchers::{header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Mock data used in the notifier test cases
+mod mock_data {
+    use super::*;
+
+    // Webhook config pointing at the mock server, with no retries unless asked for.
+    pub fn webhook_config(url: String, retries: u32) -> WebhookConfig {
+        WebhookConfig {
+            url,
+            headers: HashMap::from([("X-Api-Key".to_string(), "webhook_key".into())]),
+            secret: Some("shared_secret".into()),
+            timeout_seconds: 5,
+            retries,
+            on_interval: false,
+        }
+    }
+
+    pub fn interval_payload() -> NotificationPayload {
+        NotificationPayload {
+            event: NotificationEvent::Interval,
+            site_id: "test_site_id".to_string(),
+            sent_at: Timestamp::parse("2023-12-25T00:00:00.000Z").unwrap(),
+            alert: None,
+            intervals: Vec::new(),
+        }
+    }
+}
+
+/// Test the payload signature matches a known HMAC-SHA256 value
+#[test]
+fn webhook_signature_is_hmac_sha256() {
+    // echo -n 'hello' | openssl dgst -sha256 -hmac 'secret'
+    assert_eq!(
+        WebhookNotifier::sign("secret", b"hello"),
+        "sha256=88aab3ede8d3adf94d26ab90d3bafd4a2083070c3bcce9c014ee04a443847c0b"
+    );
+}
+
+/// Test webhooks are POSTed with the custom headers and a signature
+#[tokio::test]
+async fn webhook_posts_signed_payload_with_custom_headers() {
+    let mock_server = MockServer::start().await;
+    let notifier = WebhookNotifier::new(mock_data::webhook_config(
+        format!("{}/hook", mock_server.uri()),
+        0,
+    ))
+    .unwrap();
+
+    let payload = mock_data::interval_payload();
+    let signature = WebhookNotifier::sign(
+        "shared_secret",
+        serde_json::to_string(&payload).unwrap().as_bytes(),
+    );
+
+    Mock::given(method("POST"))
+        .and(path("/hook"))
+        .and(header("X-Api-Key", "webhook_key"))
+        .and(header("Content-Type", "application/json"))
+        .and(header(SIGNATURE_HEADER, signature.as_str()))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    notifier.send(&payload).await.unwrap();
+}
+
+/// Test a failing webhook is retried until it succeeds
+#[tokio::test]
+async fn webhook_retries_failed_requests() {
+    let mock_server = MockServer::start().await;
+    let notifier = WebhookNotifier::new(mock_data::webhook_config(mock_server.uri(), 2)).unwrap();
+
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    notifier.send(&mock_data::interval_payload()).await.unwrap();
+}
+
+/// Test the error is returned once the retries are used up
+#[tokio::test]
+#[should_panic(expected = "503 Service Unavailable")]
+async fn webhook_gives_up_after_retries() {
+    let mock_server = MockServer::start().await;
+    let notifier = WebhookNotifier::new(mock_data::webhook_config(mock_server.uri(), 1)).unwrap();
+
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(503))
+        .expect(2)
+        .mount(&mock_server)
+        .await;
+
+    notifier.send(&mock_data::interval_payload()).await.unwrap();
+}
@@ -0,0 +1,123 @@
+use amber_client::calendar::to_ics;
+use amber_client::periods::{find_price_periods, PeriodKind, PeriodThresholds};
+use amber_client::rest_client::PriceData;
+use iso8601_timestamp::Timestamp;
+
+/// Mock data used in the price period test cases
+mod mock_data {
+    use amber_client::rest_client::PriceData;
+
+    // Build a general channel interval starting `index` half hours after 00:00 UTC (10:00 NEM time).
+    fn price_interval(index: usize, per_kwh: f32, descriptor: &str) -> String {
+        let interval_type = match index {
+            0 => "CurrentInterval",
+            _ => "ForecastInterval",
+        };
+        let start_minutes = index * 30;
+        let end_minutes = start_minutes + 30;
+        format!(
+            r#"{{
+              "type": "{interval_type}",
+              "date": "2023-12-25T00:00:00.000Z",
+              "duration": 30,
+              "startTime": "2023-12-25T{:02}:{:02}:01.000Z",
+              "endTime": "2023-12-25T{:02}:{:02}:00.000Z",
+              "nemTime": "2023-12-25T{:02}:{:02}:00.000Z",
+              "perKwh": {per_kwh},
+              "renewables": 50.0,
+              "spotPerKwh": 10.0,
+              "channelType": "general",
+              "spikeStatus": "none",
+              "tariffInformation": {{ "period": "peak" }},
+              "descriptor": "{descriptor}"
+            }}"#,
+            start_minutes / 60,
+            start_minutes % 60,
+            end_minutes / 60,
+            end_minutes % 60,
+            end_minutes / 60,
+            end_minutes % 60,
+        )
+    }
+
+    pub fn forecast() -> Vec<PriceData> {
+        let intervals: Vec<String> = [
+            (20.0, "neutral"),
+            (5.0, "veryLow"),
+            (3.4, "extremelyLow"),
+            (18.0, "neutral"),
+            (95.0, "spike"),
+            (120.0, "spike"),
+            (25.0, "high"),
+        ]
+        .iter()
+        .enumerate()
+        .map(|(index, (price, descriptor))| price_interval(index, *price, descriptor))
+        .collect();
+        serde_json::from_str(&format!("[{}]", intervals.join(","))).unwrap()
+    }
+}
+
+/// Test consecutive intervals are grouped by Amber's descriptor
+#[test]
+fn periods_from_descriptors() {
+    let prices: Vec<PriceData> = mock_data::forecast();
+    let periods = find_price_periods(&prices, "general", &PeriodThresholds::default());
+
+    assert_eq!(periods.len(), 2);
+    assert_eq!(periods[0].kind, PeriodKind::Cheap);
+    assert_eq!(periods[0].intervals, 2);
+    assert_eq!(periods[0].average_per_kwh, 4.2);
+    assert_eq!(periods[1].kind, PeriodKind::Spike);
+    assert_eq!(
+        periods[1].start_time.to_string(),
+        "2023-12-25T02:00:01.000Z"
+    );
+    assert_eq!(periods[1].end_time.to_string(), "2023-12-25T03:00:00.000Z");
+}
+
+/// Test user thresholds replace the descriptors
+#[test]
+fn periods_from_thresholds() {
+    let prices: Vec<PriceData> = mock_data::forecast();
+    let thresholds = PeriodThresholds {
+        cheap_below: Some(19.0),
+        spike_above: Some(100.0),
+    };
+    let periods = find_price_periods(&prices, "general", &thresholds);
+
+    assert_eq!(periods.len(), 2);
+    assert_eq!(periods[0].kind, PeriodKind::Cheap);
+    assert_eq!(periods[0].intervals, 3);
+    assert_eq!(periods[1].kind, PeriodKind::Spike);
+    assert_eq!(periods[1].intervals, 1);
+    assert_eq!(periods[1].average_per_kwh, 120.0);
+}
+
+/// Test the calendar events have readable summaries and UIDs that do not change between runs
+#[test]
+fn periods_calendar() {
+    let prices: Vec<PriceData> = mock_data::forecast();
+    let periods = find_price_periods(&prices, "general", &PeriodThresholds::default());
+    let events: Vec<_> = periods
+        .iter()
+        .map(|period| period.calendar_event())
+        .collect();
+
+    assert_eq!(events[0].summary, "Cheap power 10:30–11:30, avg 4.2c");
+    assert_eq!(events[1].summary, "Price spike 12:00–13:00, avg 107.5c");
+    assert_eq!(
+        events[0].uid,
+        "price-cheap-general-20231225T003000Z@amber-cli"
+    );
+
+    // Regenerating the calendar with a longer forecast keeps the same UIDs.
+    let again = find_price_periods(&prices[..6], "general", &PeriodThresholds::default());
+    assert_eq!(again[0].calendar_event().uid, events[0].uid);
+    assert_eq!(again[1].calendar_event().uid, events[1].uid);
+
+    let now = Timestamp::parse("2023-12-25T00:00:00Z").unwrap();
+    let calendar = to_ics(&events, &now);
+    assert_eq!(calendar.matches("BEGIN:VEVENT").count(), 2);
+    assert!(calendar.contains("DTSTART:20231225T003000Z\r\nDTEND:20231225T013000Z\r\n"));
+}
@@ -0,0 +1,161 @@
+use amber_client::planner::{find_cheapest_window, parse_duration_minutes, Objective, PlanOptions};
+use amber_client::rest_client::PriceData;
+
+/// Mock data used in the planner test cases
+mod mock_data {
+    use amber_client::rest_client::PriceData;
+
+    // Build a general channel interval starting `index` half hours after 06:00.
+    fn price_interval(index: usize, per_kwh: f32, renewables: f32) -> String {
+        let interval_type = match index {
+            0 => "CurrentInterval",
+            _ => "ForecastInterval",
+        };
+        let start_minutes = 6 * 60 + index * 30;
+        let end_minutes = start_minutes + 30;
+        format!(
+            r#"{{
+              "type": "{interval_type}",
+              "date": "2023-12-25T00:00:00.000Z",
+              "duration": 30,
+              "startTime": "2023-12-25T{:02}:{:02}:01.000Z",
+              "endTime": "2023-12-25T{:02}:{:02}:00.000Z",
+              "nemTime": "2023-12-25T{:02}:{:02}:00.000Z",
+              "perKwh": {per_kwh},
+              "renewables": {renewables},
+              "spotPerKwh": 10.0,
+              "channelType": "general",
+              "spikeStatus": "none",
+              "tariffInformation": {{ "period": "peak" }},
+              "descriptor": "neutral"
+            }}"#,
+            start_minutes / 60,
+            start_minutes % 60,
+            end_minutes / 60,
+            end_minutes % 60,
+            end_minutes / 60,
+            end_minutes % 60,
+        )
+    }
+
+    // Six intervals from 06:00, the two cheapest are not next to each other
+    // and the greenest are at the end.
+    pub fn forecast() -> Vec<PriceData> {
+        let intervals: Vec<String> = [
+            (30.0, 20.0),
+            (10.0, 30.0),
+            (25.0, 40.0),
+            (12.0, 50.0),
+            (14.0, 80.0),
+            (40.0, 90.0),
+        ]
+        .iter()
+        .enumerate()
+        .map(|(index, (price, renewables))| price_interval(index, *price, *renewables))
+        .collect();
+        serde_json::from_str(&format!("[{}]", intervals.join(","))).unwrap()
+    }
+}
+
+fn options(duration_minutes: u32, contiguous: bool, objective: Objective) -> PlanOptions {
+    PlanOptions {
+        duration_minutes,
+        within_minutes: 12 * 60,
+        channel_type: "general".to_string(),
+        contiguous,
+        objective,
+        carbon_weight: 0.2,
+        power_kw: Some(2.0),
+    }
+}
+
+/// Test the duration formats accepted by the planner
+#[test]
+fn parse_durations() {
+    assert_eq!(parse_duration_minutes("2h").unwrap(), 120);
+    assert_eq!(parse_duration_minutes("90m").unwrap(), 90);
+    assert_eq!(parse_duration_minutes("1h30m").unwrap(), 90);
+    assert_eq!(parse_duration_minutes("45").unwrap(), 45);
+    assert!(parse_duration_minutes("2 hours").is_err());
+    assert!(parse_duration_minutes("0h").is_err());
+    assert!(parse_duration_minutes("71582789h").is_err());
+    assert!(parse_duration_minutes("4294967295m1m").is_err());
+    assert!(parse_duration_minutes("4294967296").is_err());
+}
+
+/// Test a contiguous load picks the cheapest run of consecutive intervals
+#[test]
+fn cheapest_contiguous_window() {
+    let prices: Vec<PriceData> = mock_data::forecast();
+    let plan = find_cheapest_window(&prices, &options(60, true, Objective::Price)).unwrap();
+
+    assert_eq!(plan.runs.len(), 1);
+    assert_eq!(
+        plan.runs[0].start_time.to_string(),
+        "2023-12-25T07:30:01.000Z"
+    );
+    assert_eq!(plan.runs[0].intervals, 2);
+    assert_eq!(plan.average_per_kwh, 13.0);
+    assert_eq!(plan.now_average_per_kwh, 20.0);
+    assert_eq!(plan.savings_per_kwh, 7.0);
+    assert_eq!(plan.savings_percent, 35.0);
+    // 2kW for an hour at 13c/kWh average
+    assert_eq!(plan.estimated_cost, Some(26.0));
+    assert_eq!(plan.estimated_savings, Some(14.0));
+}
+
+/// Test a split load uses the cheapest intervals wherever they are
+#[test]
+fn cheapest_split_intervals() {
+    let prices: Vec<PriceData> = mock_data::forecast();
+    let plan = find_cheapest_window(&prices, &options(60, false, Objective::Price)).unwrap();
+
+    assert_eq!(plan.runs.len(), 2);
+    assert_eq!(
+        plan.runs[0].start_time.to_string(),
+        "2023-12-25T06:30:01.000Z"
+    );
+    assert_eq!(
+        plan.runs[1].start_time.to_string(),
+        "2023-12-25T07:30:01.000Z"
+    );
+    assert_eq!(plan.average_per_kwh, 11.0);
+}
+
+/// Test the renewables objective picks the greenest intervals
+#[test]
+fn greenest_window() {
+    let prices: Vec<PriceData> = mock_data::forecast();
+    let plan = find_cheapest_window(&prices, &options(60, true, Objective::Renewables)).unwrap();
+
+    assert_eq!(
+        plan.runs[0].start_time.to_string(),
+        "2023-12-25T08:00:01.000Z"
+    );
+    assert_eq!(plan.average_renewables, 85.0);
+    assert_eq!(plan.now_average_renewables, 25.0);
+}
+
+/// Test the weighted objective trades price off against renewables
+#[test]
+fn weighted_split_intervals() {
+    let prices: Vec<PriceData> = mock_data::forecast();
+    let plan = find_cheapest_window(&prices, &options(60, false, Objective::Weighted)).unwrap();
+
+    // 12c at 50% and 14c at 80% beat the cheaper 10c at 30%.
+    assert_eq!(plan.runs.len(), 1);
+    assert_eq!(
+        plan.runs[0].start_time.to_string(),
+        "2023-12-25T07:30:01.000Z"
+    );
+    assert_eq!(plan.average_per_kwh, 13.0);
+    assert_eq!(plan.average_renewables, 65.0);
+}
+
+/// Test loads longer than the forecast are rejected
+#[test]
+fn not_enough_forecast() {
+    let prices: Vec<PriceData> = mock_data::forecast();
+    assert!(find_cheapest_window(&prices, &options(3 * 60, true, Objective::Price)).is_ok());
+    assert!(find_cheapest_window(&prices, &options(3 * 60 + 30, true, Objective::Price)).is_err());
+}
@@ -0,0 +1,84 @@
+use amber_client::app_config::AppConfig;
+
+/// Mock data used in the profile test cases
+mod mock_data {
+    // Our account, and a parent's account in another state with its own token.
+    pub fn config() -> String {
+        r#"
+[amberconfig]
+base_url = "https://api.amber.com.au/v1"
+
+[default.userconfig]
+state = "vic"
+
+[default.apitoken]
+name = "shared"
+psk = "psk_default"
+
+[profiles.home.userconfig]
+state = "vic"
+site_id = "home_site"
+
+[profiles.mum.userconfig]
+state = "qld"
+
+[profiles.mum.apitoken]
+psk = "psk_mum"
+"#
+        .to_string()
+    }
+}
+
+fn write_config(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("amber-cli-profiles-{}.toml", name));
+    std::fs::write(&path, mock_data::config()).unwrap();
+    path.display().to_string()
+}
+
+/// Test the selected profile is merged over the [default] profile, key by key
+#[tokio::test]
+async fn merge_profiles() {
+    let path = write_config("merge");
+
+    let default = AppConfig::get(Some(path.clone()), None).await.unwrap();
+    assert_eq!(default.userconfig.state, "vic");
+    assert_eq!(default.apitoken.psk.expose_secret(), "psk_default");
+
+    let mum = AppConfig::get(Some(path.clone()), Some("mum"))
+        .await
+        .unwrap();
+    assert_eq!(mum.amberconfig.base_url, "https://api.amber.com.au/v1");
+    assert_eq!(mum.userconfig.state, "qld");
+    assert_eq!(mum.apitoken.name, "shared");
+    assert_eq!(mum.apitoken.psk.expose_secret(), "psk_mum");
+
+    let home = AppConfig::get(Some(path), Some("home")).await.unwrap();
+    assert_eq!(home.userconfig.site_id.as_deref(), Some("home_site"));
+    assert_eq!(home.apitoken.psk.expose_secret(), "psk_default");
+}
+
+/// Test an unknown profile lists the profiles there are
+#[tokio::test]
+async fn unknown_profile() {
+    let path = write_config("unknown");
+    let error = AppConfig::get(Some(path), Some("dad"))
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("No [profiles.dad]"));
+    assert!(error.contains("the profiles are: home, mum"));
+}
+
+/// Test the profiles are listed with the [default] profile merged in
+#[test]
+fn list_profiles() {
+    let path = write_config("list");
+    let profiles = AppConfig::profiles(Some(&path)).unwrap();
+
+    assert_eq!(profiles.len(), 2);
+    assert_eq!(profiles[0].name, "home");
+    assert_eq!(profiles[0].site_id.as_deref(), Some("home_site"));
+    assert_eq!(profiles[1].name, "mum");
+    assert_eq!(profiles[1].token_name.as_deref(), Some("shared"));
+    assert_eq!(profiles[1].state.as_deref(), Some("qld"));
+}
@@ -0,0 +1,174 @@
+use amber_client::proxy::{
+    handle_request, usage_has_ended, ProxyState, CACHE_HEADER, MAX_WINDOW_INTERVALS,
+};
+
+use hyper::{Body, Request, StatusCode};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Mock data used in the proxy test cases
+mod mock_data {
+    // Raw JSON for a single current price interval.
+    pub fn price_json() -> String {
+        r#"[{
+            "type": "CurrentInterval",
+            "date": "2023-12-25T00:00:00.000Z",
+            "duration": 30,
+            "startTime": "2023-12-24T22:30:01.000Z",
+            "endTime": "2023-12-24T23:00:00.000Z",
+            "nemTime": "2023-12-24T23:00:00.000Z",
+            "perKwh": 5.5,
+            "renewables": 73.5,
+            "spotPerKwh": -4.5,
+            "channelType": "general",
+            "spikeStatus": "none",
+            "tariffInformation": { "period": "offPeak" },
+            "descriptor": "extremelyLow",
+            "estimate": true
+        }]"#
+        .to_string()
+    }
+}
+
+fn proxy_state(base_url: String) -> Arc<ProxyState> {
+    Arc::new(ProxyState::new(
+        base_url,
+        "token".into(),
+        "test_site_id".to_string(),
+        "vic".to_string(),
+    ))
+}
+
+fn get(uri: &str) -> Request<Body> {
+    Request::get(uri).body(Body::empty()).unwrap()
+}
+
+/// Test repeated requests in the same interval are answered from the cache
+#[tokio::test]
+async fn prices_are_cached_for_the_interval() {
+    let mock_server = MockServer::start().await;
+    let state = proxy_state(mock_server.uri());
+
+    Mock::given(method("GET"))
+        .and(path("/sites/test_site_id/prices/current"))
+        .and(query_param("next", "2"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_raw(mock_data::price_json(), "application/json"),
+        )
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let first = handle_request(state.clone(), get("/prices/current?next=2")).await;
+    assert_eq!(first.status(), StatusCode::OK);
+    assert_eq!(first.headers()[CACHE_HEADER], "MISS");
+
+    let second = handle_request(state, get("/prices/current?next=2")).await;
+    assert_eq!(second.headers()[CACHE_HEADER], "HIT");
+    let body = hyper::body::to_bytes(second.into_body()).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body[0]["perKwh"], 5.5);
+}
+
+/// Test a slow upstream request only holds up clients of the same route
+#[tokio::test]
+async fn slow_upstream_only_blocks_its_own_route() {
+    let mock_server = MockServer::start().await;
+    let state = proxy_state(mock_server.uri());
+
+    Mock::given(method("GET"))
+        .and(path("/sites/test_site_id/usage"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_raw("[]", "application/json")
+                .set_delay(Duration::from_secs(2)),
+        )
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/sites/test_site_id/prices/current"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_raw(mock_data::price_json(), "application/json"),
+        )
+        .mount(&mock_server)
+        .await;
+
+    let usage = "/usage?startDate=2023-12-01&endDate=2023-12-02";
+    let slow = tokio::spawn(handle_request(state.clone(), get(usage)));
+    let shared = tokio::spawn(handle_request(state.clone(), get(usage)));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let started = Instant::now();
+    let prices = handle_request(state, get("/prices/current")).await;
+    assert_eq!(prices.status(), StatusCode::OK);
+    assert!(started.elapsed() < Duration::from_secs(1));
+
+    let mut cache_headers = vec![
+        slow.await.unwrap().headers()[CACHE_HEADER].clone(),
+        shared.await.unwrap().headers()[CACHE_HEADER].clone(),
+    ];
+    cache_headers.sort();
+    assert_eq!(cache_headers, ["HIT", "MISS"]);
+}
+
+/// Test invalid usage dates are rejected without querying Amber
+#[tokio::test]
+async fn usage_requires_valid_dates() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+
+    let response = handle_request(
+        proxy_state(mock_server.uri()),
+        get("/usage?startDate=2023-13-01&endDate=2023-12-02"),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+/// Test windows longer than the limit are rejected without querying Amber
+#[tokio::test]
+async fn prices_window_is_limited() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+
+    let uri = format!("/prices/current?next={}", MAX_WINDOW_INTERVALS + 1);
+    let response = handle_request(proxy_state(mock_server.uri()), get(&uri)).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+/// Test usage is only treated as final once its end date has passed in NEM time
+#[test]
+fn usage_ends_after_its_end_date() {
+    // 2023-12-25T13:00:00Z is 23:00 on the 25th in NEM time.
+    assert!(!usage_has_ended("2023-12-25", 1_703_509_200));
+    // 2023-12-25T14:00:00Z is midnight on the 26th in NEM time.
+    assert!(usage_has_ended("2023-12-25", 1_703_512_800));
+    assert!(!usage_has_ended("2023-12-26", 1_703_512_800));
+}
+
+/// Test upstream failures are reported as a bad gateway
+#[tokio::test]
+async fn upstream_errors_are_bad_gateway() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(401).set_body_string(r#"{"message": "Unauthorized"}"#))
+        .mount(&mock_server)
+        .await;
+
+    let response = handle_request(proxy_state(mock_server.uri()), get("/sites")).await;
+    assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+}
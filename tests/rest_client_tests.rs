@@ -32,7 +32,7 @@ mod mock_data {
 
     // Raw JSON test data for 'site-details".
     pub fn amber_site_details_json() -> String {
-        let new_amber_site_details_json = r#"[
+        r#"[
           {
             "activeFrom": "2023-08-31T00:00:00.000Z",
             "channels": [
@@ -48,8 +48,7 @@ mod mock_data {
             "status": "active"
           }
        ]"#
-        .to_string();
-        return new_amber_site_details_json;
+        .to_string()
     }
 
     // Mock return code for unauthorized access to Amber's REST API
@@ -63,7 +62,7 @@ mod mock_data {
 async fn ensure_correct_headers_are_present_and_get_called_once() {
     let mock_server = MockServer::start().await;
 
-    let mut user_site_details = RestClient::new_client(mock_server.uri(), "token".to_string());
+    let mut user_site_details = RestClient::new_client(mock_server.uri(), "token".into());
 
     Mock::given(header("AUTHORIZATION", "Bearer token"))
         .and(header("CONTENT_TYPE", "application/json"))
@@ -81,7 +80,7 @@ async fn valid_json_parsing_for_site_details() {
     let mock_server = MockServer::start().await;
     let template = ResponseTemplate::new(200)
         .set_body_raw(mock_data::amber_site_details_json(), "application/json");
-    let mut user_site_details = RestClient::new_client(mock_server.uri(), "token".to_string());
+    let mut user_site_details = RestClient::new_client(mock_server.uri(), "token".into());
 
     Mock::given(method("GET"))
         .respond_with(template)
@@ -105,7 +104,7 @@ async fn unauthorized_api_access() {
     let mock_server = MockServer::start().await;
     let template = ResponseTemplate::new(401)
         .set_body_raw(mock_data::amber_401_unauthorized(), "application/json");
-    let mut unauthorized_access = RestClient::new_client(mock_server.uri(), "token".to_string());
+    let mut unauthorized_access = RestClient::new_client(mock_server.uri(), "token".into());
 
     Mock::given(method("GET"))
         .respond_with(template)
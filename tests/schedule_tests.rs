@@ -0,0 +1,216 @@
+use amber_client::calendar::to_ics;
+use amber_client::rest_client::PriceData;
+use amber_client::schedule::{resolve_time, schedule_loads, ResolvedLoad};
+use iso8601_timestamp::Timestamp;
+
+/// Mock data used in the schedule test cases
+mod mock_data {
+    use amber_client::rest_client::PriceData;
+
+    // Build a general channel interval starting `index` half hours after 00:00 UTC.
+    fn price_interval(index: usize, per_kwh: f32) -> String {
+        let interval_type = match index {
+            0 => "CurrentInterval",
+            _ => "ForecastInterval",
+        };
+        let start_minutes = index * 30;
+        let end_minutes = start_minutes + 30;
+        let time = |minutes: usize| {
+            format!(
+                "2023-12-{:02}T{:02}:{:02}",
+                25 + minutes / 1440,
+                minutes / 60 % 24,
+                minutes % 60
+            )
+        };
+        format!(
+            r#"{{
+              "type": "{interval_type}",
+              "date": "2023-12-25T00:00:00.000Z",
+              "duration": 30,
+              "startTime": "{}:01.000Z",
+              "endTime": "{}:00.000Z",
+              "nemTime": "{}:00.000Z",
+              "perKwh": {per_kwh},
+              "renewables": 50.0,
+              "spotPerKwh": 10.0,
+              "channelType": "general",
+              "spikeStatus": "none",
+              "tariffInformation": {{ "period": "peak" }},
+              "descriptor": "neutral"
+            }}"#,
+            time(start_minutes),
+            time(end_minutes),
+            time(end_minutes),
+        )
+    }
+
+    // Eight intervals from 00:00 UTC, cheapest at 01:00-02:00 and 03:00.
+    pub fn forecast() -> Vec<PriceData> {
+        let intervals: Vec<String> = [30.0, 25.0, 5.0, 6.0, 20.0, 22.0, 4.0, 35.0]
+            .iter()
+            .enumerate()
+            .map(|(index, price)| price_interval(index, *price))
+            .collect();
+        serde_json::from_str(&format!("[{}]", intervals.join(","))).unwrap()
+    }
+
+    // A day of intervals from 00:00 UTC, cheapest in the middle of the day.
+    pub fn day_forecast() -> Vec<PriceData> {
+        let intervals: Vec<String> = (0..48)
+            .map(|index| price_interval(index, 5.0 + (index as f32 - 24.0).abs()))
+            .collect();
+        serde_json::from_str(&format!("[{}]", intervals.join(","))).unwrap()
+    }
+}
+
+fn load(name: &str, power_kw: f32, duration_minutes: u32, interruptible: bool) -> ResolvedLoad {
+    ResolvedLoad {
+        name: name.to_string(),
+        power_kw,
+        duration_minutes,
+        earliest_start: None,
+        deadline: None,
+        interruptible,
+    }
+}
+
+/// Test "HH:MM" times are resolved in NEM time, to the next occurrence
+#[test]
+fn resolve_clock_times() {
+    // 09:00 NEM time on the 25th.
+    let now = Timestamp::parse("2023-12-24T23:00:00Z").unwrap();
+    assert_eq!(
+        resolve_time("11:30", &now).unwrap(),
+        Timestamp::parse("2023-12-25T01:30:00Z").unwrap()
+    );
+    // 08:00 has passed, so it is tomorrow morning.
+    assert_eq!(
+        resolve_time("08:00", &now).unwrap(),
+        Timestamp::parse("2023-12-25T22:00:00Z").unwrap()
+    );
+    assert_eq!(
+        resolve_time("2023-12-25T03:00:00Z", &now).unwrap(),
+        Timestamp::parse("2023-12-25T03:00:00Z").unwrap()
+    );
+    assert!(resolve_time("9am", &now).is_err());
+}
+
+/// Test each load is placed in its cheapest window
+#[test]
+fn schedule_independent_loads() {
+    let prices: Vec<PriceData> = mock_data::forecast();
+    let loads = vec![
+        load("dishwasher", 2.0, 60, false),
+        load("pool pump", 1.0, 60, true),
+    ];
+    let schedule = schedule_loads(&prices, "general", &loads, None).unwrap();
+
+    assert_eq!(schedule.loads[0].name, "dishwasher");
+    assert_eq!(
+        schedule.loads[0].start_time.to_string(),
+        "2023-12-25T01:00:01.000Z"
+    );
+    assert_eq!(schedule.loads[0].estimated_cost, 11.0);
+    // The pool pump can be paused, so it takes the two cheapest intervals.
+    assert_eq!(schedule.loads[1].runs.len(), 2);
+    assert_eq!(
+        schedule.loads[1].runs[0].start_time.to_string(),
+        "2023-12-25T01:00:01.000Z"
+    );
+    assert_eq!(
+        schedule.loads[1].runs[1].start_time.to_string(),
+        "2023-12-25T03:00:01.000Z"
+    );
+    assert_eq!(schedule.total_cost, 11.0 + 4.5);
+}
+
+/// Test loads are kept under the power limit and inside their deadline
+#[test]
+fn schedule_with_power_limit_and_deadline() {
+    let prices: Vec<PriceData> = mock_data::forecast();
+    let mut dryer = load("dryer", 2.0, 60, false);
+    dryer.deadline = Some(Timestamp::parse("2023-12-25T02:30:00Z").unwrap());
+    let loads = vec![load("dishwasher", 2.0, 60, false), dryer];
+    let schedule = schedule_loads(&prices, "general", &loads, Some(3.0)).unwrap();
+
+    // The dryer has to finish by 02:30, so it gets 01:00 and the dishwasher runs after it.
+    assert_eq!(
+        schedule.loads[1].start_time.to_string(),
+        "2023-12-25T01:00:01.000Z"
+    );
+    assert_eq!(
+        schedule.loads[0].start_time.to_string(),
+        "2023-12-25T02:30:01.000Z"
+    );
+
+    // Both loads can not fit before the deadline.
+    let mut washer = load("washer", 2.0, 90, false);
+    washer.deadline = Some(Timestamp::parse("2023-12-25T01:00:00Z").unwrap());
+    assert!(schedule_loads(&prices, "general", &[washer], None).is_err());
+}
+
+/// Test competing loads are shared out at the lowest total cost, not first come first served
+#[test]
+fn schedule_lowest_total_cost() {
+    let prices: Vec<PriceData> = mock_data::forecast();
+    let loads = vec![load("fan", 1.0, 30, true), load("heater", 3.0, 30, true)];
+    let schedule = schedule_loads(&prices, "general", &loads, Some(3.0)).unwrap();
+
+    // The heater draws more, so it gets the cheapest interval at 03:00 and the fan 01:00.
+    assert_eq!(
+        schedule.loads[1].start_time.to_string(),
+        "2023-12-25T03:00:01.000Z"
+    );
+    assert_eq!(
+        schedule.loads[0].start_time.to_string(),
+        "2023-12-25T01:00:01.000Z"
+    );
+    assert_eq!(schedule.total_cost, 6.0 + 2.5);
+}
+
+/// Test many long loads are still scheduled quickly and under the power limit
+#[test]
+fn schedule_many_long_loads() {
+    let prices: Vec<PriceData> = mock_data::day_forecast();
+    let loads: Vec<ResolvedLoad> = (0..8)
+        .map(|n| load(&format!("load {}", n), 1.0, 240, true))
+        .collect();
+    let started = std::time::Instant::now();
+    let schedule = schedule_loads(&prices, "general", &loads, Some(3.0)).unwrap();
+    assert!(started.elapsed() < std::time::Duration::from_secs(1));
+
+    let mut running = std::collections::HashMap::new();
+    for load in &schedule.loads {
+        assert_eq!(load.runs.iter().map(|run| run.intervals).sum::<usize>(), 8);
+        for run in &load.runs {
+            let start = prices
+                .iter()
+                .position(|price| price.start_time == run.start_time)
+                .unwrap();
+            for index in start..start + run.intervals {
+                *running.entry(index).or_insert(0) += 1;
+            }
+        }
+    }
+    assert!(running.values().all(|loads| *loads <= 3));
+}
+
+/// Test the calendar file has an event per run, with stable UIDs
+#[test]
+fn schedule_calendar() {
+    let prices: Vec<PriceData> = mock_data::forecast();
+    let loads = vec![load("Pool Pump", 1.0, 60, true)];
+    let schedule = schedule_loads(&prices, "general", &loads, None).unwrap();
+    let now = Timestamp::parse("2023-12-24T23:00:00Z").unwrap();
+    let calendar = to_ics(&schedule.calendar_events(), &now);
+
+    assert!(calendar.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+    assert!(calendar.ends_with("END:VCALENDAR\r\n"));
+    assert_eq!(calendar.matches("BEGIN:VEVENT").count(), 2);
+    assert!(calendar.contains("UID:plan-pool-pump-20231225-1@amber-cli\r\n"));
+    assert!(calendar.contains("DTSTART:20231225T010000Z\r\nDTEND:20231225T013000Z\r\n"));
+    assert!(calendar.contains("SUMMARY:Run Pool Pump\r\n"));
+    assert!(calendar.contains("DESCRIPTION:1 kW\\, average 4.50c/kWh"));
+    assert!(calendar.lines().all(|line| line.len() <= 75));
+}
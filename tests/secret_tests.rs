@@ -0,0 +1,197 @@
+use amber_client::app_config::AppConfig;
+use amber_client::rest_client::RestClient;
+use amber_client::secret::SecretString;
+use amber_client::{get_prices, get_site_data, get_spike_status};
+
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use tracing::debug;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// API token the tests check never shows up in the logs.
+const PSK: &str = "psk_supersecret0123456789";
+
+/// Mock data used in the secret test cases
+mod mock_data {
+    // Raw JSON for a single site.
+    pub fn site_details_json() -> String {
+        r#"[
+          {
+            "activeFrom": "2023-08-31T00:00:00.000Z",
+            "channels": [{ "identifier": "E1", "tariff": "A123", "type": "general" }],
+            "id": "test_site_id",
+            "network": "Jemena",
+            "nmi": "1234567890",
+            "status": "active"
+          }
+        ]"#
+        .to_string()
+    }
+
+    // Raw JSON for the current interval.
+    pub fn current_price_json() -> String {
+        r#"[
+          {
+            "type": "CurrentInterval",
+            "date": "2023-12-25T00:00:00.000Z",
+            "duration": 30,
+            "startTime": "2023-12-25T06:00:01.000Z",
+            "endTime": "2023-12-25T06:30:00.000Z",
+            "nemTime": "2023-12-25T06:30:00.000Z",
+            "perKwh": 25.0,
+            "renewables": 40.0,
+            "spotPerKwh": 10.0,
+            "channelType": "general",
+            "spikeStatus": "none",
+            "tariffInformation": { "period": "peak" },
+            "descriptor": "neutral"
+          }
+        ]"#
+        .to_string()
+    }
+
+    pub fn config(base_url: &str, psk: &str) -> String {
+        format!(
+            r#"[amberconfig]
+base_url = "{base_url}"
+
+[userconfig]
+state = "vic"
+
+[apitoken]
+name = "test token"
+psk = "{psk}"
+
+[influxdb]
+url = "http://localhost:8086"
+org = "home"
+bucket = "amber"
+token = "influx_supersecret"
+
+[[webhook]]
+url = "https://ntfy.sh/amber"
+[webhook.headers]
+Authorization = "Bearer webhook_supersecret"
+"#
+        )
+    }
+}
+
+/// Writer that keeps everything logged, for the tests to search.
+#[derive(Clone, Default)]
+struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+impl Write for CapturedLogs {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl CapturedLogs {
+    fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).to_string()
+    }
+}
+
+/// Test that Debug and Display never show the secret, but it can still be used.
+#[test]
+fn secret_string_is_redacted() {
+    let secret = SecretString::from(PSK);
+
+    assert_eq!(format!("{}", secret), "[REDACTED]");
+    assert_eq!(format!("{:?}", secret), "SecretString([REDACTED])");
+    assert_eq!(secret.expose_secret(), PSK);
+
+    let client = RestClient::new_client("http://localhost".to_string(), secret);
+    assert!(!format!("{:?}", client).contains(PSK));
+}
+
+/// Test that the API token never appears in the DEBUG logs of requests, errors or the config.
+#[tokio::test(flavor = "current_thread")]
+async fn token_is_not_logged() {
+    let logs = CapturedLogs::default();
+    let writer = logs.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .with_ansi(false)
+        .with_writer(move || writer.clone())
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let mock_server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/sites"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_raw(mock_data::site_details_json(), "application/json"),
+        )
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/sites/test_site_id/prices/current"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_raw(mock_data::current_price_json(), "application/json"),
+        )
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/sites/unknown_site/prices/current"))
+        .respond_with(
+            ResponseTemplate::new(401)
+                .set_body_raw(r#"{"message": "Unauthorized"}"#, "application/json"),
+        )
+        .mount(&mock_server)
+        .await;
+
+    let base_url = mock_server.uri();
+    get_site_data(base_url.clone(), PSK.into()).await.unwrap();
+    get_prices(
+        base_url.clone(),
+        PSK.into(),
+        "test_site_id".to_string(),
+        "current".to_string(),
+    )
+    .await
+    .unwrap();
+    let spike_status = get_spike_status(base_url.clone(), PSK.into(), "test_site_id".to_string())
+        .await
+        .unwrap();
+    assert_eq!(spike_status, "Interval has no spike");
+
+    let error = get_spike_status(base_url.clone(), PSK.into(), "unknown_site".to_string())
+        .await
+        .unwrap_err();
+    debug!("Request failed: {:?}", error);
+    assert!(!format!("{:#}", error).contains(PSK));
+
+    let config_file = std::env::temp_dir().join("amber-cli-secret-test.toml");
+    std::fs::write(
+        &config_file,
+        mock_data::config("https://api.amber.com.au/v1", PSK),
+    )
+    .unwrap();
+    let config = AppConfig::get(Some(config_file.to_string_lossy().to_string()), None)
+        .await
+        .unwrap();
+    std::fs::remove_file(&config_file).unwrap();
+    assert_eq!(config.apitoken.psk.expose_secret(), PSK);
+    assert_eq!(
+        config.webhook[0].headers["Authorization"].expose_secret(),
+        "Bearer webhook_supersecret"
+    );
+    debug!("Loaded config: {:?}", config);
+
+    let logs = logs.contents();
+    assert!(logs.contains("Loaded config"));
+    assert!(logs.contains("[REDACTED]"));
+    assert!(!logs.contains(PSK));
+    assert!(!logs.contains("influx_supersecret"));
+    assert!(!logs.contains("webhook_supersecret"));
+}
@@ -0,0 +1,112 @@
+use amber_client::rest_client::{PriceData, UsageData};
+use amber_client::solar::build_solar_report;
+use amber_client::watch::{evaluate_alerts, AlertKind, AlertState, WatchOptions};
+
+/// Mock data used in the solar test cases
+mod mock_data {
+    // Build a usage interval starting at `start` UTC on 2023-12-25.
+    pub fn usage_interval(start: &str, channel_type: &str, kwh: f32, per_kwh: f32) -> String {
+        format!(
+            r#"{{
+              "type": "Usage",
+              "duration": 30,
+              "date": "2023-12-25T00:00:00.000Z",
+              "endTime": "2023-12-25T{start}:00.000Z",
+              "quality": "billable",
+              "kwh": {kwh},
+              "nemTime": "2023-12-25T{start}:00.000Z",
+              "perKwh": {per_kwh},
+              "channelType": "{channel_type}",
+              "channelIdentifier": "E1",
+              "cost": {cost},
+              "renewables": 50.0,
+              "spotPerKwh": 10.0,
+              "startTime": "2023-12-25T{start}:01.000Z",
+              "spikeStatus": "none",
+              "tariffInformation": {{ "period": "peak" }},
+              "descriptor": "neutral"
+            }}"#,
+            cost = kwh * per_kwh
+        )
+    }
+
+    // Build a current feed in price interval.
+    pub fn feed_in_price(start: &str, per_kwh: f32) -> String {
+        format!(
+            r#"{{
+              "type": "CurrentInterval",
+              "date": "2023-12-25T00:00:00.000Z",
+              "duration": 30,
+              "startTime": "2023-12-25T{start}:01.000Z",
+              "endTime": "2023-12-25T{start}:00.000Z",
+              "nemTime": "2023-12-25T{start}:00.000Z",
+              "perKwh": {per_kwh},
+              "renewables": 90.0,
+              "spotPerKwh": -5.0,
+              "channelType": "feedIn",
+              "spikeStatus": "none",
+              "tariffInformation": {{ "period": "peak" }},
+              "descriptor": "extremelyLow"
+            }}"#
+        )
+    }
+}
+
+fn usage() -> Vec<UsageData> {
+    let intervals = [
+        mock_data::usage_interval("00:00", "feedIn", 2.0, -8.0),
+        mock_data::usage_interval("02:00", "feedIn", 3.0, 2.0),
+        mock_data::usage_interval("02:30", "feedIn", 4.0, 3.0),
+        mock_data::usage_interval("03:00", "feedIn", 1.0, 1.0),
+        mock_data::usage_interval("03:00", "general", 0.5, 10.0),
+    ];
+    serde_json::from_str(&format!("[{}]", intervals.join(","))).unwrap()
+}
+
+/// Test export earnings, and the intervals and hours where exporting cost money
+#[test]
+fn solar_report() {
+    let report = build_solar_report(&usage());
+
+    assert_eq!(report.channels.len(), 2);
+    assert_eq!(report.export_kwh, 10.0);
+    // Earned 16c, then paid 6c, 12c and 1c to export.
+    assert_eq!(report.export_earnings, 16.0 - 19.0);
+    assert_eq!(report.negative_intervals.len(), 3);
+    assert_eq!(report.negative_kwh, 8.0);
+    assert_eq!(report.negative_cost, 19.0);
+
+    // 02:00 and 02:30 UTC are both 12:00 NEM time.
+    assert_eq!(report.curtail_hours.len(), 2);
+    assert_eq!(report.curtail_hours[0].hour, "12:00");
+    assert_eq!(report.curtail_hours[0].intervals, 2);
+    assert_eq!(report.curtail_hours[0].cost, 18.0);
+    assert_eq!(report.curtail_hours[1].hour, "13:00");
+}
+
+/// Test watch mode alerts once when exporting starts costing money, and again when it stops
+#[test]
+fn feed_in_costs_money_alert() {
+    let options = WatchOptions {
+        forecast_intervals: 12,
+        channel_type: "general".to_string(),
+        price_limit: None,
+        once: true,
+    };
+    let prices = |per_kwh: f32| -> Vec<PriceData> {
+        serde_json::from_str(&format!("[{}]", mock_data::feed_in_price("02:00", per_kwh))).unwrap()
+    };
+    let mut state = AlertState::default();
+
+    assert!(evaluate_alerts(&prices(-6.0), &options, &mut state).is_empty());
+
+    let costs_money = evaluate_alerts(&prices(1.5), &options, &mut state);
+    assert_eq!(costs_money.len(), 1);
+    assert_eq!(costs_money[0].kind, AlertKind::FeedInCostsMoney);
+    assert!(costs_money[0].message.contains("costs 1.50 c/kWh"));
+    assert!(evaluate_alerts(&prices(2.0), &options, &mut state).is_empty());
+
+    let recovered = evaluate_alerts(&prices(-3.0), &options, &mut state);
+    assert_eq!(recovered.len(), 1);
+    assert_eq!(recovered[0].kind, AlertKind::FeedInRecovered);
+}
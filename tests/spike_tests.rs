@@ -0,0 +1,327 @@
+use amber_client::get_spike_forecast;
+use amber_client::spike::find_forecast_spike;
+use amber_client::watch::{
+    duration_until_next_interval, evaluate_alerts, AlertKind, AlertState, WatchOptions,
+};
+
+use std::time::Duration;
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Mock data used in the spike test cases
+mod mock_data {
+    use amber_client::rest_client::PriceData;
+
+    // Build a single price interval as Amber would return it.
+    fn price_interval(
+        interval_type: &str,
+        start_time: &str,
+        end_time: &str,
+        per_kwh: f32,
+        spike_status: &str,
+    ) -> String {
+        format!(
+            r#"{{
+              "type": "{interval_type}",
+              "date": "2023-12-25T00:00:00.000Z",
+              "duration": 30,
+              "startTime": "{start_time}",
+              "endTime": "{end_time}",
+              "nemTime": "{end_time}",
+              "perKwh": {per_kwh},
+              "renewables": 40.0,
+              "spotPerKwh": 10.0,
+              "channelType": "general",
+              "spikeStatus": "{spike_status}",
+              "tariffInformation": {{ "period": "peak" }},
+              "descriptor": "high"
+            }}"#
+        )
+    }
+
+    // Raw JSON for a current interval followed by four forecast intervals,
+    // with a two interval spike in the middle of the forecast.
+    pub fn forecast_with_spike_json() -> String {
+        let intervals = [
+            price_interval(
+                "CurrentInterval",
+                "2023-12-25T06:00:01.000Z",
+                "2023-12-25T06:30:00.000Z",
+                25.0,
+                "none",
+            ),
+            price_interval(
+                "ForecastInterval",
+                "2023-12-25T06:30:01.000Z",
+                "2023-12-25T07:00:00.000Z",
+                28.0,
+                "none",
+            ),
+            price_interval(
+                "ForecastInterval",
+                "2023-12-25T07:00:01.000Z",
+                "2023-12-25T07:30:00.000Z",
+                95.0,
+                "potential",
+            ),
+            price_interval(
+                "ForecastInterval",
+                "2023-12-25T07:30:01.000Z",
+                "2023-12-25T08:00:00.000Z",
+                180.0,
+                "spike",
+            ),
+            price_interval(
+                "ForecastInterval",
+                "2023-12-25T08:00:01.000Z",
+                "2023-12-25T08:30:00.000Z",
+                30.0,
+                "none",
+            ),
+        ];
+        format!("[{}]", intervals.join(","))
+    }
+
+    pub fn forecast_with_spike() -> Vec<PriceData> {
+        serde_json::from_str(&forecast_with_spike_json()).unwrap()
+    }
+
+    // Two consecutive polls of a three interval spike, by the second poll the first
+    // spiking interval has become the current interval.
+    pub fn consecutive_polls_of_spike() -> (Vec<PriceData>, Vec<PriceData>) {
+        let spike_intervals = [
+            ("2023-12-25T07:00:01.000Z", "2023-12-25T07:30:00.000Z"),
+            ("2023-12-25T07:30:01.000Z", "2023-12-25T08:00:00.000Z"),
+            ("2023-12-25T08:00:01.000Z", "2023-12-25T08:30:00.000Z"),
+        ];
+        let after_spike = price_interval(
+            "ForecastInterval",
+            "2023-12-25T08:30:01.000Z",
+            "2023-12-25T09:00:00.000Z",
+            30.0,
+            "none",
+        );
+
+        let mut first = vec![price_interval(
+            "CurrentInterval",
+            "2023-12-25T06:30:01.000Z",
+            "2023-12-25T07:00:00.000Z",
+            25.0,
+            "none",
+        )];
+        for (start_time, end_time) in spike_intervals {
+            first.push(price_interval(
+                "ForecastInterval",
+                start_time,
+                end_time,
+                150.0,
+                "spike",
+            ));
+        }
+        first.push(after_spike.clone());
+
+        let mut second = vec![price_interval(
+            "CurrentInterval",
+            spike_intervals[0].0,
+            spike_intervals[0].1,
+            150.0,
+            "spike",
+        )];
+        for (start_time, end_time) in &spike_intervals[1..] {
+            second.push(price_interval(
+                "ForecastInterval",
+                start_time,
+                end_time,
+                150.0,
+                "spike",
+            ));
+        }
+        second.push(after_spike);
+
+        let parse = |intervals: Vec<String>| {
+            serde_json::from_str(&format!("[{}]", intervals.join(","))).unwrap()
+        };
+        (parse(first), parse(second))
+    }
+
+    // Two polls while a spike is current, with a second spike after a non-spiking interval.
+    pub fn polls_of_two_spikes() -> (Vec<PriceData>, Vec<PriceData>) {
+        let second_spike = price_interval(
+            "ForecastInterval",
+            "2023-12-25T08:00:01.000Z",
+            "2023-12-25T08:30:00.000Z",
+            150.0,
+            "spike",
+        );
+        let first = vec![
+            price_interval(
+                "CurrentInterval",
+                "2023-12-25T07:00:01.000Z",
+                "2023-12-25T07:30:00.000Z",
+                150.0,
+                "spike",
+            ),
+            price_interval(
+                "ForecastInterval",
+                "2023-12-25T07:30:01.000Z",
+                "2023-12-25T08:00:00.000Z",
+                30.0,
+                "none",
+            ),
+            second_spike.clone(),
+        ];
+        let second = vec![
+            price_interval(
+                "CurrentInterval",
+                "2023-12-25T07:30:01.000Z",
+                "2023-12-25T08:00:00.000Z",
+                30.0,
+                "none",
+            ),
+            second_spike,
+        ];
+
+        let parse = |intervals: Vec<String>| {
+            serde_json::from_str(&format!("[{}]", intervals.join(","))).unwrap()
+        };
+        (parse(first), parse(second))
+    }
+}
+
+/// Test the first run of spiking forecast intervals is merged into one spike window
+#[test]
+fn forecast_spike_covers_consecutive_spiking_intervals() {
+    let spike = find_forecast_spike(&mock_data::forecast_with_spike(), "general", None).unwrap();
+
+    assert_eq!(spike.start_time.to_string(), "2023-12-25T07:00:01.000Z");
+    assert_eq!(spike.end_time.to_string(), "2023-12-25T08:00:00.000Z");
+    assert_eq!(spike.duration_minutes, 60);
+    assert_eq!(spike.intervals, 2);
+    assert_eq!(spike.spike_status, "spike");
+    assert_eq!(spike.max_per_kwh, 180.0);
+}
+
+/// Test a price limit flags intervals Amber does not consider a spike
+#[test]
+fn forecast_spike_uses_price_limit() {
+    let spike =
+        find_forecast_spike(&mock_data::forecast_with_spike(), "general", Some(27.0)).unwrap();
+
+    assert_eq!(spike.start_time.to_string(), "2023-12-25T06:30:01.000Z");
+    assert_eq!(spike.intervals, 4);
+}
+
+/// Test no spike is reported for a channel without forecast data
+#[test]
+fn forecast_spike_ignores_other_channels() {
+    assert!(find_forecast_spike(&mock_data::forecast_with_spike(), "feedIn", None).is_none());
+}
+
+/// Test watch mode only alerts once for the same forecast spike
+#[test]
+fn watch_alerts_are_not_repeated() {
+    let options = WatchOptions {
+        forecast_intervals: 12,
+        channel_type: "general".to_string(),
+        price_limit: None,
+        once: true,
+    };
+    let mut state = AlertState::default();
+    let prices = mock_data::forecast_with_spike();
+
+    let first = evaluate_alerts(&prices, &options, &mut state);
+    assert_eq!(first.len(), 1);
+    assert_eq!(first[0].kind, AlertKind::ForecastSpike);
+
+    let second = evaluate_alerts(&prices, &options, &mut state);
+    assert!(second.is_empty());
+}
+
+/// Test a forecast spike is not alerted again once its first interval becomes current
+#[test]
+fn watch_alerts_once_as_spike_becomes_current() {
+    let options = WatchOptions {
+        forecast_intervals: 12,
+        channel_type: "general".to_string(),
+        price_limit: None,
+        once: false,
+    };
+    let mut state = AlertState::default();
+    let (first_poll, second_poll) = mock_data::consecutive_polls_of_spike();
+
+    let first = evaluate_alerts(&first_poll, &options, &mut state);
+    assert_eq!(first.len(), 1);
+    assert_eq!(first[0].kind, AlertKind::ForecastSpike);
+    assert_eq!(first[0].spike.as_ref().unwrap().intervals, 3);
+
+    // The rest of the run is still forecast, but it is the spike already alerted on.
+    let second = evaluate_alerts(&second_poll, &options, &mut state);
+    assert_eq!(second.len(), 1);
+    assert_eq!(second[0].kind, AlertKind::CurrentSpike);
+
+    assert!(evaluate_alerts(&second_poll, &options, &mut state).is_empty());
+}
+
+/// Test a separate spike forecast while another is current is still alerted, once
+#[test]
+fn watch_alerts_separate_spike_while_one_is_current() {
+    let options = WatchOptions {
+        forecast_intervals: 12,
+        channel_type: "general".to_string(),
+        price_limit: None,
+        once: false,
+    };
+    let mut state = AlertState::default();
+    let (first_poll, second_poll) = mock_data::polls_of_two_spikes();
+
+    let first = evaluate_alerts(&first_poll, &options, &mut state);
+    assert_eq!(first.len(), 2);
+    assert_eq!(first[0].kind, AlertKind::CurrentSpike);
+    assert_eq!(first[1].kind, AlertKind::ForecastSpike);
+    assert_eq!(
+        first[1].spike.as_ref().unwrap().start_time.to_string(),
+        "2023-12-25T08:00:01.000Z"
+    );
+
+    assert!(evaluate_alerts(&second_poll, &options, &mut state).is_empty());
+}
+
+/// Test watch mode wakes just after the next interval boundary
+#[test]
+fn watch_sleeps_until_next_interval() {
+    // 2023-12-25T06:10:00Z
+    assert_eq!(
+        duration_until_next_interval(1_703_484_600),
+        Duration::from_secs(20 * 60 + 30)
+    );
+}
+
+/// Test the spike forecast requests the number of forecast intervals asked for
+#[tokio::test]
+async fn spike_forecast_requests_next_intervals() {
+    let mock_server = MockServer::start().await;
+    let template = ResponseTemplate::new(200)
+        .set_body_raw(mock_data::forecast_with_spike_json(), "application/json");
+
+    Mock::given(method("GET"))
+        .and(path("/sites/test_site_id/prices/current"))
+        .and(query_param("next", "4"))
+        .and(query_param("resolution", "30"))
+        .respond_with(template)
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let spike = get_spike_forecast(
+        mock_server.uri(),
+        "token".into(),
+        "test_site_id".to_string(),
+        4,
+        "general".to_string(),
+        None,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(spike.unwrap().intervals, 2);
+}
@@ -0,0 +1,157 @@
+use amber_client::app_config::BillingConfig;
+use amber_client::rest_client::UsageData;
+use amber_client::tariff::{compare_plan, RetailPlan, TariffsFile};
+use std::fs;
+
+/// Mock data used in the tariff test cases
+mod mock_data {
+    // Build a usage interval starting at `start` UTC, on the NEM `date`.
+    fn usage_interval(date: &str, start: &str, channel_type: &str, kwh: f32, cost: f32) -> String {
+        format!(
+            r#"{{
+              "type": "Usage",
+              "duration": 30,
+              "date": "{date}T00:00:00.000Z",
+              "endTime": "{start}:00.000Z",
+              "quality": "billable",
+              "kwh": {kwh},
+              "nemTime": "{start}:00.000Z",
+              "perKwh": 20.0,
+              "channelType": "{channel_type}",
+              "channelIdentifier": "E1",
+              "cost": {cost},
+              "renewables": 50.0,
+              "spotPerKwh": 10.0,
+              "startTime": "{start}:01.000Z",
+              "spikeStatus": "none",
+              "tariffInformation": {{ "period": "peak" }},
+              "descriptor": "neutral"
+            }}"#
+        )
+    }
+
+    // Friday 2023-12-29 and Monday 2024-01-01, times are UTC so 06:00 UTC is 16:00 NEM time.
+    pub fn usage() -> String {
+        let intervals = [
+            usage_interval("2023-12-29", "2023-12-29T06:00", "general", 2.0, 60.0),
+            usage_interval("2023-12-29", "2023-12-29T13:00", "general", 1.0, 10.0),
+            usage_interval(
+                "2023-12-29",
+                "2023-12-29T14:00",
+                "controlledLoad",
+                3.0,
+                30.0,
+            ),
+            usage_interval("2023-12-29", "2023-12-29T02:00", "feedIn", 4.0, -12.0),
+            usage_interval("2024-01-01", "2024-01-01T06:00", "general", 1.0, 30.0),
+        ];
+        format!("[{}]", intervals.join(","))
+    }
+
+    pub const TARIFFS: &str = r#"
+[[plan]]
+name = "Flat rate"
+daily_supply_charge = 100.0
+general_rate = 30.0
+feed_in_tariff = 5.0
+
+[[plan]]
+name = "Time of use"
+daily_supply_charge = 110.0
+general_rate = 20.0
+controlled_load_rate = 15.0
+controlled_load_supply_charge = 4.0
+
+[[plan.period]]
+name = "peak"
+rate = 50.0
+start = "15:00"
+end = "21:00"
+days = ["weekdays"]
+
+[[plan.period]]
+name = "off peak"
+rate = 12.0
+start = "22:00"
+end = "07:00"
+"#;
+}
+
+fn usage() -> Vec<UsageData> {
+    serde_json::from_str(&mock_data::usage()).unwrap()
+}
+
+fn tariffs(name: &str, contents: &str) -> anyhow::Result<TariffsFile> {
+    let file = std::env::temp_dir().join(format!("amber-cli-{}.toml", name));
+    fs::write(&file, contents).unwrap();
+    TariffsFile::read(&file.display().to_string())
+}
+
+fn plan(name: &str) -> RetailPlan {
+    tariffs("tariffs", mock_data::TARIFFS)
+        .unwrap()
+        .plans
+        .into_iter()
+        .find(|plan| plan.name == name)
+        .unwrap()
+}
+
+/// Test a flat rate plan, controlled load falls back to the general rate
+#[test]
+fn compare_flat_rate() {
+    let comparison = compare_plan(&usage(), &plan("Flat rate"), None).unwrap();
+
+    assert_eq!(comparison.months.len(), 2);
+    let december = &comparison.months[0];
+    assert_eq!(december.month, "2023-12");
+    assert_eq!(december.days, 1);
+    assert_eq!(december.amber_cost, 88.0);
+    // 6 kWh at 30c, less 4 kWh at 5c, plus one day of supply
+    assert_eq!(december.plan_cost, 180.0 - 20.0 + 100.0);
+    assert_eq!(december.difference, 260.0 - 88.0);
+    assert_eq!(comparison.plan_cost, 260.0 + 130.0);
+    assert_eq!(comparison.amber_cost, 118.0);
+}
+
+/// Test time of use windows by weekday and across midnight, and controlled load charges
+#[test]
+fn compare_time_of_use() {
+    let comparison = compare_plan(&usage(), &plan("Time of use"), None).unwrap();
+
+    // 16:00 Friday peak, 23:00 off peak, controlled load at 00:00, no feed in tariff.
+    let december = &comparison.months[0];
+    assert_eq!(december.plan_cost, 100.0 + 12.0 + 45.0 + 110.0 + 4.0);
+    // 16:00 Monday is peak too.
+    assert_eq!(comparison.months[1].plan_cost, 50.0 + 110.0);
+}
+
+/// Test Amber's fixed charges from the [billing] config are included
+#[test]
+fn compare_with_billing() {
+    let billing = BillingConfig {
+        daily_supply_charge: 90.0,
+        monthly_membership_fee: 365.0 / 12.0 * 10.0,
+    };
+    let comparison = compare_plan(&usage(), &plan("Flat rate"), Some(&billing)).unwrap();
+    assert_eq!(comparison.months[0].amber_cost.round(), 88.0 + 100.0);
+}
+
+/// Test plans that can not price every interval are rejected
+#[test]
+fn invalid_tariffs() {
+    assert!(tariffs("empty", "").is_err());
+    assert!(tariffs("no-rate", "[[plan]]\nname = \"none\"\n").is_err());
+    assert!(tariffs(
+        "bad-day",
+        "[[plan]]\nname = \"bad\"\ngeneral_rate = 1.0\n[[plan.period]]\nname = \"p\"\nrate = 2.0\nstart = \"15:00\"\nend = \"21:00\"\ndays = [\"someday\"]\n"
+    )
+    .is_err());
+
+    // Without a general rate, usage outside every period can not be priced.
+    let gaps = tariffs(
+        "gaps",
+        "[[plan]]\nname = \"gaps\"\n[[plan.period]]\nname = \"p\"\nrate = 2.0\nstart = \"15:00\"\nend = \"21:00\"\n",
+    )
+    .unwrap();
+    assert!(compare_plan(&usage(), &gaps.plans[0], None).is_err());
+}
@@ -0,0 +1,180 @@
+use amber_client::rest_client::UsageData;
+use amber_client::usage_report::{aggregate_usage, renewables_share, GroupBy, UsageGroup};
+
+/// Mock data used in the usage report test cases
+mod mock_data {
+    // Build a usage interval starting at `start` UTC on `date`.
+    fn usage_interval(
+        date: &str,
+        start: &str,
+        channel_type: &str,
+        kwh: f32,
+        cost: f32,
+        spot_per_kwh: f32,
+        renewables: f32,
+    ) -> String {
+        format!(
+            r#"{{
+              "type": "Usage",
+              "duration": 30,
+              "date": "{date}T00:00:00.000Z",
+              "endTime": "{start}:00.000Z",
+              "quality": "billable",
+              "kwh": {kwh},
+              "nemTime": "{start}:00.000Z",
+              "perKwh": 20.0,
+              "channelType": "{channel_type}",
+              "channelIdentifier": "E1",
+              "cost": {cost},
+              "renewables": {renewables},
+              "spotPerKwh": {spot_per_kwh},
+              "startTime": "{start}:01.000Z",
+              "spikeStatus": "none",
+              "tariffInformation": {{ "period": "peak" }},
+              "descriptor": "neutral"
+            }}"#
+        )
+    }
+
+    // Friday the 29th to Monday the 1st, across a month and ISO week boundary.
+    pub fn usage() -> String {
+        let intervals = [
+            usage_interval(
+                "2023-12-29",
+                "2023-12-28T21:00",
+                "general",
+                1.0,
+                30.0,
+                10.0,
+                20.0,
+            ),
+            usage_interval(
+                "2023-12-29",
+                "2023-12-28T21:30",
+                "general",
+                3.0,
+                30.0,
+                2.0,
+                60.0,
+            ),
+            usage_interval(
+                "2023-12-29",
+                "2023-12-29T08:00",
+                "general",
+                2.0,
+                40.0,
+                8.0,
+                40.0,
+            ),
+            usage_interval(
+                "2023-12-29",
+                "2023-12-29T02:00",
+                "feedIn",
+                4.0,
+                -20.0,
+                8.0,
+                80.0,
+            ),
+            usage_interval(
+                "2024-01-01",
+                "2023-12-31T21:00",
+                "general",
+                2.0,
+                20.0,
+                5.0,
+                50.0,
+            ),
+        ];
+        format!("[{}]", intervals.join(","))
+    }
+}
+
+fn usage() -> Vec<UsageData> {
+    serde_json::from_str(&mock_data::usage()).unwrap()
+}
+
+fn general(report: &[UsageGroup]) -> Vec<&UsageGroup> {
+    report
+        .iter()
+        .filter(|row| row.channel_type == "general")
+        .collect()
+}
+
+/// Test daily totals and the weighted averages
+#[test]
+fn group_by_day() {
+    let report = aggregate_usage(&usage(), GroupBy::Day);
+    let general = general(&report);
+
+    assert_eq!(report.len(), 3);
+    assert_eq!(general[0].group, "2023-12-29");
+    assert_eq!(general[0].intervals, 3);
+    assert_eq!(general[0].kwh, 6.0);
+    assert_eq!(general[0].cost, 100.0);
+    assert_eq!(general[0].average_per_kwh, 100.0 / 6.0);
+    // (10 * 1 + 2 * 3 + 8 * 2) / 6
+    assert_eq!(general[0].average_spot_per_kwh, 32.0 / 6.0);
+    // (20 * 1 + 60 * 3 + 40 * 2) / 6
+    assert_eq!(general[0].renewables, 280.0 / 6.0);
+
+    let feed_in = report
+        .iter()
+        .find(|row| row.channel_type == "feedIn")
+        .unwrap();
+    assert_eq!(feed_in.average_per_kwh, -5.0);
+}
+
+/// Test weeks and months use the NEM date of each interval
+#[test]
+fn group_by_week_and_month() {
+    let weeks = aggregate_usage(&usage(), GroupBy::Week);
+    let weeks: Vec<&str> = general(&weeks)
+        .iter()
+        .map(|row| row.group.as_str())
+        .collect();
+    assert_eq!(weeks, vec!["2023-W52", "2024-W01"]);
+
+    let months = aggregate_usage(&usage(), GroupBy::Month);
+    let months: Vec<(&str, f32)> = general(&months)
+        .iter()
+        .map(|row| (row.group.as_str(), row.kwh))
+        .collect();
+    assert_eq!(months, vec![("2023-12", 6.0), ("2024-01", 2.0)]);
+}
+
+/// Test the hour of day profile is in NEM time, and weekdays sort Monday first
+#[test]
+fn group_by_hour_of_day_and_weekday() {
+    let hours = aggregate_usage(&usage(), GroupBy::HourOfDay);
+    let hours: Vec<(&str, f32)> = general(&hours)
+        .iter()
+        .map(|row| (row.group.as_str(), row.kwh))
+        .collect();
+    assert_eq!(hours, vec![("07:00", 6.0), ("18:00", 2.0)]);
+
+    let weekdays = aggregate_usage(&usage(), GroupBy::Weekday);
+    let weekdays: Vec<&str> = general(&weekdays)
+        .iter()
+        .map(|row| row.group.as_str())
+        .collect();
+    assert_eq!(weekdays, vec!["Monday", "Friday"]);
+}
+
+/// Test the renewables share is weighted by usage and compared to the grid average
+#[test]
+fn renewables_share_by_day() {
+    let report = renewables_share(&usage(), GroupBy::Day);
+
+    // Feed in is not usage, so the 29th only has the three general intervals.
+    assert_eq!(report.len(), 2);
+    assert_eq!(report[0].group, "2023-12-29");
+    assert_eq!(report[0].kwh, 6.0);
+    // 1 * 20% + 3 * 60% + 2 * 40%
+    assert!((report[0].renewable_kwh - 2.8).abs() < 1e-4);
+    assert!((report[0].renewables - 280.0 / 6.0).abs() < 1e-4);
+    assert_eq!(report[0].grid_renewables, 40.0);
+    assert!((report[0].shift - 40.0 / 6.0).abs() < 1e-4);
+
+    assert_eq!(report[1].renewables, 50.0);
+    assert_eq!(report[1].shift, 0.0);
+}